@@ -0,0 +1,70 @@
+// Measures hit testing with `TriangulatedData::get_intersection` on synthetic datasets of growing
+// size, compared to testing every polygon in turn. Run using `cargo bench`.
+
+use std::f64::consts::PI;
+use std::time::Instant;
+
+use geo_draw::{LocationData, TriangulatedData};
+
+const VERTICES: usize = 64;
+const QUERIES: usize = 100_000;
+// The linear scan is too slow for the full number of queries on the larger datasets
+const LINEAR_QUERIES: usize = 1_000;
+
+// Builds a location in the binary format of `data-extract`, containing a single star shaped ring.
+fn build_location(cx: f64, cy: f64, radius: f64) -> LocationData {
+    let mut raw = b"location\0".to_vec();
+    raw.extend(1i32.to_le_bytes());
+    raw.extend(1i32.to_le_bytes());
+    raw.extend((VERTICES as i32).to_le_bytes());
+    for i in 0..VERTICES {
+        let angle = 2.0 * PI * i as f64 / VERTICES as f64;
+        let r = if i % 2 == 0 { radius } else { 0.7 * radius };
+        raw.extend((((cx + r * angle.cos()) * 1e7) as i32).to_le_bytes());
+        raw.extend((((cy + r * angle.sin()) * 1e7) as i32).to_le_bytes());
+    }
+    LocationData::parse_location_data("location".to_owned(), &raw)
+}
+
+fn main() {
+    for size in [10, 40, 160] {
+        let step = 60.0 / size as f64;
+        let locs: Vec<LocationData> = (0..size * size)
+            .map(|i| build_location((i % size) as f64 * step, (i / size) as f64 * step, step / 2.0))
+            .collect();
+        let mut data = TriangulatedData::new();
+        for loc in &locs {
+            data.add_location(loc);
+        }
+        data.triangulate(false);
+        let (min, max) = (data.min(), data.max());
+        let mut seed = 42u32;
+        let mut random = || {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            seed as f32 / u32::MAX as f32
+        };
+        let points: Vec<Vec<f32>> = (0..QUERIES)
+            .map(|_| vec![min[0] + random() * (max[0] - min[0]), min[1] + random() * (max[1] - min[1])])
+            .collect();
+        let start = Instant::now();
+        let hits = points.iter().filter(|p| data.get_intersection(p.to_vec(), 0.0).is_some()).count();
+        let elapsed = start.elapsed();
+        println!(
+            "{:6} locations: {:8.3} us/query indexed ({} hits)",
+            size * size, elapsed.as_secs_f64() * 1e6 / QUERIES as f64, hits
+        );
+
+        let polygons: Vec<_> = locs.iter()
+            .flat_map(|loc| (0..loc.count_polygons()).filter_map(|i| loc.get_polygon(i)))
+            .collect();
+        let start = Instant::now();
+        let hits = points[..LINEAR_QUERIES].iter()
+            .filter(|p| polygons.iter().any(|poly| poly.contains(p)))
+            .count();
+        let elapsed = start.elapsed();
+        println!(
+            "{:6} locations: {:8.3} us/query linear ({} hits)",
+            size * size, elapsed.as_secs_f64() * 1e6 / LINEAR_QUERIES as f64, hits
+        );
+    }
+}
@@ -9,6 +9,8 @@ use std::char;
 use std::collections::HashMap;
 use std::io::prelude::*;
 
+use geo_draw::{LocationData, AdjacencyGraph};
+
 fn not_alphabetic(c: char) -> bool {
     return !c.is_alphabetic();
 }
@@ -75,7 +77,7 @@ fn generate_name(locations: &HashMap<String, (String, String)>, parents: str::Sp
     return ret.trim_matches(|c| c == ' ' || c == ',').to_owned();
 }
 
-fn write_i32(file: &mut File, value: i32) {
+fn write_i32(file: &mut impl Write, value: i32) {
     file.write_all(&[
         (value & 0xff) as u8,
         ((value >> 8) & 0xff) as u8,
@@ -87,6 +89,9 @@ fn write_i32(file: &mut File, value: i32) {
 const MAX_POINTS_PER_PATH: i32 = 256;
 const MAX_POLY_PARTS: i32 = 16;
 const MAX_POLYGONS: i32 = 16;
+const ADJACENCY_TOLERANCE: f32 = 0.5; // in kilometres
+const DISSOLVE_TOLERANCE: f32 = 0.5; // in kilometres
+const GROUPS_FILE: &str = "groups.json";
 
 fn cross_product(x: &(f64, f64), y: &(f64, f64)) -> f64 {
     return (x.0 * y.1) - (x.1 * y.0);
@@ -105,7 +110,7 @@ fn polygon_outer_size(poly: &json::JsonValue) -> i64 {
     return (f64::abs(ret) * 1_000_000.0) as i64;
 }
 
-fn write_poly_part(file: &mut File, part: &json::JsonValue) {
+fn write_poly_part(file: &mut impl Write, part: &json::JsonValue) {
     if part.len() as i32 <= MAX_POINTS_PER_PATH {
         write_i32(file, part.len() as i32); // number of coordinates
         for coords in part.members() {
@@ -152,7 +157,7 @@ fn write_poly_part(file: &mut File, part: &json::JsonValue) {
     }
 }
 
-fn write_polygon(file: &mut File, poly: &json::JsonValue) {
+fn write_polygon(file: &mut impl Write, poly: &json::JsonValue) {
     if poly.len() as i32 <= MAX_POLY_PARTS {
         write_i32(file, poly.len() as i32); // number of paths
         for part in poly.members() {
@@ -170,16 +175,15 @@ fn write_polygon(file: &mut File, poly: &json::JsonValue) {
     }
 }
 
-fn write_to_file(id: &str, name: &str, geom: &json::JsonValue) {
+fn write_to_file(id: &str, name: &str, geom: &json::JsonValue) -> Option<Vec<u8>> {
+    let mut file = Vec::new();
     let coordinates = &geom["coordinates"];
     if geom["type"] == "Polygon" {
-        let mut file = File::create(format!("../static/data/{}.bin", id)).unwrap();
         file.write_all(name.as_bytes()).unwrap();
         file.write_all(&[0]).unwrap();
         write_i32(&mut file, 1); // number of polygons
         write_polygon(&mut file, coordinates);
     } else if geom["type"] == "MultiPolygon" {
-        let mut file = File::create(format!("../static/data/{}.bin", id)).unwrap();
         file.write_all(name.as_bytes()).unwrap();
         file.write_all(&[0]).unwrap();
         if coordinates.len() as i32 <= MAX_POLYGONS {
@@ -207,7 +211,6 @@ fn write_to_file(id: &str, name: &str, geom: &json::JsonValue) {
                 }
             }
         }
-        let mut file = File::create(format!("../static/data/{}.bin", id)).unwrap();
         file.write_all(name.as_bytes()).unwrap();
         file.write_all(&[0]).unwrap();
         if polys.len() as i32 <= MAX_POLYGONS {
@@ -225,13 +228,17 @@ fn write_to_file(id: &str, name: &str, geom: &json::JsonValue) {
         }
     } else {
         println!("Different geometry type: {:?}", geom["type"]);
+        return None;
     }
+    File::create(format!("../static/data/{}.bin", id)).unwrap().write_all(&file).unwrap();
+    Some(file)
 }
 
 fn generate_data(
     names: &mut HashMap<String, String>, 
     fragments: &mut HashMap<String, HashMap<String, i32>>,
     locations: &HashMap<String, (String, String)>, 
+    geometry: &mut Vec<(String, usize, LocationData)>,
     geojson: &json::JsonValue
 ) {
     if geojson["type"] == "FeatureCollection" {
@@ -241,21 +248,27 @@ fn generate_data(
                     // Data exported from geoboundaries.org
                     let id = features["properties"]["shapeID"].as_str().unwrap_or("").to_string();
                     let parents = features["properties"]["ADMHIERACHY"].as_str().unwrap_or(&id).split(",");
+                    let level = parents.clone().count();
                     let name = generate_name(locations, parents.clone(), false);
                     let frag_name = generate_name(locations, parents, true);
                     generate_fragments(fragments, id.to_string(), &frag_name);
                     names.insert(id.to_string(), name.clone());
-                    write_to_file(&id, &name, &features["geometry"]);
+                    if let Some(raw) = write_to_file(&id, &name, &features["geometry"]) {
+                        geometry.push((id.clone(), level, LocationData::parse_location_data(id, &raw)));
+                    }
                 } else if !features["properties"]["id"].is_null() {
                     let id = features["properties"]["id"].as_i32().unwrap().to_string();
                     // Data exported from OpenStreetMap
                     let parents = format!("{},{}", id, features["properties"]["parents"].as_str().unwrap_or(""));
                     let split_parents = parents.split(",");
+                    let level = split_parents.clone().filter(|p| !p.is_empty()).count();
                     let name = generate_name(locations, split_parents.clone(), false);
                     let frag_name = generate_name(locations, split_parents, true);
                     generate_fragments(fragments, id.to_string(), &frag_name);
                     names.insert(id.to_string(), name.clone());
-                    write_to_file(&id, &name, &features["geometry"]);
+                    if let Some(raw) = write_to_file(&id, &name, &features["geometry"]) {
+                        geometry.push((id.clone(), level, LocationData::parse_location_data(id, &raw)));
+                    }
                 } else {
                     println!("Id can't be found");
                 }
@@ -266,6 +279,70 @@ fn generate_data(
     }
 }
 
+fn generate_groups(
+    names: &mut HashMap<String, String>, 
+    fragments: &mut HashMap<String, HashMap<String, i32>>,
+    geometry: &mut Vec<(String, usize, LocationData)>,
+    groups: &json::JsonValue
+) {
+    // Groups are given as a list of { "id": ..., "name": ..., "locations": [ ... ] }
+    for group in groups.members() {
+        let id = group["id"].as_str().unwrap_or("").to_string();
+        let name = group["name"].as_str().unwrap_or(&id).to_string();
+        let members: Vec<&LocationData> = group["locations"].members()
+            .filter_map(|loc| {
+                let loc_id = loc.as_str().map(|s| s.to_string()).unwrap_or_else(|| loc.dump());
+                let found = geometry.iter().find(|g| g.0 == loc_id);
+                if found.is_none() {
+                    println!("Unknown location in group {}: {}", id, loc_id);
+                }
+                found.map(|g| &g.2)
+            })
+            .collect();
+        let dissolved = LocationData::union_all(id.clone(), name.clone(), &members, DISSOLVE_TOLERANCE);
+        fs::write(format!("../static/data/{}.bin", id), dissolved.serialize_location_data()).unwrap();
+        generate_fragments(fragments, id.clone(), &name);
+        names.insert(id.clone(), name);
+        // Groups get their own level, they should only neighbour other groups
+        geometry.push((id, 0, dissolved));
+    }
+}
+
+fn generate_adjacency(geometry: &[(String, usize, LocationData)]) -> HashMap<String, HashMap<String, f32>> {
+    // Only locations on the same administrative level can be neighbours
+    let mut levels: HashMap<usize, Vec<&(String, usize, LocationData)>> = HashMap::new();
+    for loc in geometry {
+        levels.entry(loc.1).or_default().push(loc);
+    }
+    let mut adjacency = HashMap::new();
+    for locs in levels.values() {
+        let data: Vec<&LocationData> = locs.iter().map(|loc| &loc.2).collect();
+        let graph = AdjacencyGraph::compute(&data, ADJACENCY_TOLERANCE);
+        for (a, b, length) in graph.iter() {
+            adjacency.entry(locs[a].0.clone()).or_insert_with(HashMap::new).insert(locs[b].0.clone(), length);
+            adjacency.entry(locs[b].0.clone()).or_insert_with(HashMap::new).insert(locs[a].0.clone(), length);
+        }
+    }
+    adjacency
+}
+
+fn write_location_index(geometry: &[(String, usize, LocationData)]) {
+    // For every location its id, level and bounding box as fixed point values
+    let mut file = File::create("../static/data/index_spatial.bin").unwrap();
+    write_i32(&mut file, geometry.len() as i32);
+    for (id, level, loc) in geometry {
+        file.write_all(id.as_bytes()).unwrap();
+        file.write_all(&[0]).unwrap();
+        write_i32(&mut file, *level as i32);
+        for cord in loc.min() {
+            write_i32(&mut file, (cord as f64 * 180.0e7 / std::f64::consts::PI).floor() as i32);
+        }
+        for cord in loc.max() {
+            write_i32(&mut file, (cord as f64 * 180.0e7 / std::f64::consts::PI).ceil() as i32);
+        }
+    }
+}
+
 fn main() {
     fs::remove_dir_all("../static/data").unwrap_or(());
     fs::create_dir("../static/data").unwrap();
@@ -274,7 +351,7 @@ fn main() {
     for entry in fs::read_dir("../data/").unwrap() {
         let entry = entry.unwrap();
         let path = entry.path();
-        if path.is_file() {
+        if path.is_file() && path.file_name().unwrap() != GROUPS_FILE {
             let raw_data = fs::read(path).unwrap();
             let string_data = str::from_utf8(&raw_data).unwrap();
             let json = json::parse(&string_data).unwrap();
@@ -286,9 +363,17 @@ fn main() {
     }
     let mut names = HashMap::new();
     let mut fragments = HashMap::new();
+    let mut geometry = Vec::new();
     for json in data {
-        generate_data(&mut names, &mut fragments, &locations, &json);
+        generate_data(&mut names, &mut fragments, &locations, &mut geometry, &json);
+    }
+    if let Ok(raw_groups) = fs::read_to_string(format!("../data/{}", GROUPS_FILE)) {
+        let groups = json::parse(&raw_groups).unwrap();
+        generate_groups(&mut names, &mut fragments, &mut geometry, &groups);
     }
+    let adjacency = generate_adjacency(&geometry);
+    write_location_index(&geometry);
     fs::write("../static/data/index_names.json", format!("{}", json::stringify(names))).unwrap();
     fs::write("../static/data/index_fragments.json", format!("{}", json::stringify(fragments))).unwrap();
+    fs::write("../static/data/index_adjacency.json", json::stringify(adjacency)).unwrap();
 }
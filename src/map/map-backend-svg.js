@@ -3,12 +3,14 @@ import { LitElement, html, svg, css } from 'lit';
 import { styleMap } from 'lit/directives/style-map.js';
 
 import { map } from '../util';
+import { createProjection } from './projection';
 
 class MapBackendSvg extends LitElement {
 
     static get properties() {
         return {
-            locations: { type: Array }
+            locations: { type: Array },
+            projection: { attribute: true },
         }
     }
 
@@ -151,10 +153,15 @@ class MapBackendSvg extends LitElement {
     }
 
     render() {
+        // Locations are shared between maps, so the projected data is only reused for the same projection
+        const projection = createProjection(this.projection);
+        const proj_key = JSON.stringify(this.projection ?? null);
         this.locations.forEach(loc => {
-            if (loc && (!loc.proj_min || !loc.proj_max)) {
-                loc.proj_min = loc.raw.proj_min;
-                loc.proj_max = loc.raw.proj_max;
+            if (loc && loc.proj_key !== proj_key) {
+                loc.proj_key = proj_key;
+                loc.proj_min = loc.raw.proj_min(projection);
+                loc.proj_max = loc.raw.proj_max(projection);
+                loc.proj_polygons = null;
             }
         })
         const min = this.locations.filter(loc => loc).map(loc => loc.proj_min)
@@ -166,14 +173,15 @@ class MapBackendSvg extends LitElement {
         this.locations.forEach(loc => {
             if(loc) {
                 if (!loc.proj_polygons) {
-                    loc.proj_polygons = [...Array(loc.raw.count_polygons()).keys()]
-                        .map(i => loc.raw.get_proj_polygon(i));
+                    loc.proj_polygons = [...Array(loc.raw.count_proj_polygons(projection)).keys()]
+                        .map(i => loc.raw.get_proj_polygon(projection, i));
                 }
                 loc.svg = loc.proj_polygons.map(poly => (
                     svg`<path d="${this.svgPathForPolygon(poly, min, total_diff, max_size)}"/>`
                 ));
             }
         });
+        projection.free();
         return html`
             <svg
                 id="map"
@@ -5,12 +5,17 @@ import WebGLRenderer from './webgl-renderer';
 import WebGLRenderer3d from './webgl-renderer-3d';
 
 import { TriangulatedData } from '../../pkg/index'
+import { createProjection } from './projection';
+
+// Distance in pixels within which a location is still hovered
+const HOVER_TOLERANCE = 8;
 
 class MapBackendWebGl extends LitElement {
 
     static get properties() {
         return {
             locations: { attribute: true },
+            projection: { attribute: true },
         }
     }
 
@@ -75,6 +80,10 @@ class MapBackendWebGl extends LitElement {
         return this.renderer.clientPosToProjPos(client_pos, map_pos, this.state);
     }
     
+    clientPosToCoordinates(client_pos) {
+        return this.triangulated?.get_coordinates(this.clientPosToProjPos(client_pos));
+    }
+
     projPosToClientPos(location_pos) {
         const map = this.shadowRoot.getElementById('map');
         const map_pos = map.getBoundingClientRect();
@@ -83,15 +92,18 @@ class MapBackendWebGl extends LitElement {
 
     handleMouseMove(event) {
         const pos = this.clientPosToProjPos([event.clientX, event.clientY]);
-        const inter = this.triangulated?.get_intersection(pos, this.renderer.project());
+        const tolerance = Math.abs(
+            this.clientPosToProjPos([event.clientX + HOVER_TOLERANCE, event.clientY])[0] - pos[0]
+        );
+        const inter = this.triangulated?.get_intersection(pos, tolerance);
         if (inter) {
-            const loc = this.locations[inter[0]];
-            const polygon = this.renderer.project()
-                ? loc.raw.get_proj_polygon(inter[1])
-                : loc.raw.get_polygon(inter[1]);
+            const loc = this.locations[inter.location];
+            const polygon = this.triangulated.get_polygon(inter.location, inter.polygon);
+            inter.free();
             this.state.hover = loc.id;
             const my_event = new Event('hover');
             my_event.location = loc;
+            my_event.coordinates = this.triangulated.get_coordinates(pos);
             my_event.position = this.projPosToClientPos([
                 (polygon.min[0] + polygon.max[0]) / 2,
                 (polygon.min[1] + polygon.max[1]) / 2
@@ -106,6 +118,7 @@ class MapBackendWebGl extends LitElement {
         this.state.hover = null;
         const my_event = new Event('hover');
         my_event.location = null;
+        my_event.coordinates = null;
         my_event.position = null;
         this.dispatchEvent(my_event);
     }
@@ -157,11 +170,50 @@ class MapBackendWebGl extends LitElement {
     buildRenderData() {
         if (!this.triangulated) {
             this.triangulated = TriangulatedData.new();
-            for (const loc of this.locations) {
-                this.triangulated.add_location(loc.raw);
+            this.triangulated_raw = [];
+        }
+        const raw = this.locations.map(loc => loc.raw);
+        // The projection only applies to locations triangulated after it is set
+        const reproject = this.triangulated_projection !== this.projection;
+        if (reproject) {
+            const projection = createProjection(this.projection);
+            this.triangulated.set_projection(projection);
+            this.triangulated.set_max_edge_angle(this.projection?.max_edge_angle ?? 0);
+            projection.free();
+            this.triangulated_projection = this.projection;
+        }
+        // Remove the locations that are gone first, so that the remaining ones keep their buffers
+        let changed = reproject;
+        for (let i = this.triangulated_raw.length - 1; i >= 0; i--) {
+            if (!raw.includes(this.triangulated_raw[i])) {
+                this.triangulated.remove_location(i);
+                this.triangulated_raw.splice(i, 1);
+                changed = true;
             }
+        }
+        for (let i = 0; i < raw.length; i++) {
+            if (i >= this.triangulated_raw.length) {
+                this.triangulated.add_location(raw[i]);
+                changed = true;
+            } else if (reproject || this.triangulated_raw[i] !== raw[i]) {
+                this.triangulated.replace_location(i, raw[i]);
+                changed = true;
+            }
+        }
+        for (let i = this.triangulated_raw.length - 1; i >= raw.length; i--) {
+            this.triangulated.remove_location(i);
+            changed = true;
+        }
+        this.triangulated_raw = raw;
+        if (changed) {
             this.triangulated.triangulate(this.renderer.project());
+            for (const i of this.triangulated.failed_triangulations()) {
+                console.warn(
+                    `Triangulation of ${this.locations[i].raw.name} deviates by ${this.triangulated.triangulation_deviation(i)}`
+                );
+            }
             this.triangulated.generate_outlines(this.renderer.project());
+            this.renderer.updateBuffers(this.triangulated);
             this.state.min = this.triangulated.min;
             this.state.max = this.triangulated.max;
         }
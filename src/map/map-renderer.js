@@ -449,7 +449,7 @@ export default class MapRenderer extends LitElement {
                         const res = await fetch(`${data_location}/${location}.bin`);
                         if (res.ok) {
                             const uint8_array = new Uint8Array(await res.arrayBuffer());
-                            const data = LocationData.parse_location_data(uint8_array);
+                            const data = LocationData.parse_location_data(location, uint8_array);
                             location_cache[location] = {
                                 id: location,
                                 name: data.name,
@@ -499,12 +499,14 @@ export default class MapRenderer extends LitElement {
                     <map-backend-3d
                         id="map-backend"
                         .locations="${locations}"
+                        .projection="${data.projection}"
                         @hover="${this.handleLocationHover}"
                     ></map-backend-3d>`
                 : html`
                     <map-backend
                         id="map-backend"
                         .locations="${locations}"
+                        .projection="${data.projection}"
                         @hover="${this.handleLocationHover}"
                     ></map-backend>`
             );
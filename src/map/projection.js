@@ -0,0 +1,35 @@
+
+import { Projection, ProjectionKind } from '../../pkg/index'
+
+export function createProjection(options) {
+    // The projection is either given by name or as an object with additional parameters
+    options = typeof options === 'string' ? { kind: options } : options ?? {};
+    const projection = Projection.new(ProjectionKind[options.kind] ?? ProjectionKind.WebMercator);
+    if (options.central_meridian !== undefined) {
+        projection.set_central_meridian(options.central_meridian);
+    }
+    if (options.rotation) {
+        projection.set_rotation(...options.rotation);
+    }
+    if (options.parallels) {
+        projection.set_parallels(...options.parallels);
+    }
+    if (options.scale !== undefined) {
+        projection.set_scale(options.scale);
+    }
+    // Insets are given as `{ bounds: [min_lon, min_lat, max_lon, max_lat], location, scale, offset: [lon, lat] }`,
+    // where `location` is the id of a location to move into the inset
+    for (const inset of options.insets ?? []) {
+        const i = projection.add_inset(...inset.bounds);
+        if (inset.location !== undefined) {
+            projection.set_inset_location(i, inset.location);
+        }
+        if (inset.scale !== undefined) {
+            projection.set_inset_scale(i, inset.scale);
+        }
+        if (inset.offset) {
+            projection.set_inset_offset(i, ...inset.offset);
+        }
+    }
+    return projection;
+}
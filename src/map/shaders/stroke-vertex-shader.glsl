@@ -10,7 +10,7 @@ uniform float uWidth;
 void main() {
     gl_Position = vec4(
         (aVertexPosition + uTranslate) * uScale
-        + normalize(aVertexNormal) * uWidth * uStrokeScale,
+        + aVertexNormal * uWidth * uStrokeScale,
         0.0, 1.0
     );
 }
@@ -297,19 +297,9 @@ export default class WebGLRenderer3d extends WebGLRenderer {
         gl.useProgram(fill_data.shader_program);
         gl.uniform2fv(fill_data.scale_uniform, scale);
         gl.uniform1i(fill_data.colors_uniform, 0);
-            
-        gl.bindBuffer(gl.ARRAY_BUFFER, triangles.position_buffer);
-        gl.vertexAttribPointer(fill_data.position_attribute, 2, gl.FLOAT, false, 0, 0);
-        gl.enableVertexAttribArray(fill_data.position_attribute);
-
-        gl.bindBuffer(gl.ARRAY_BUFFER, triangles.color_buffer);
-        gl.vertexAttribPointer(fill_data.color_attribute, 1, gl.FLOAT, false, 0, 0);
-        gl.enableVertexAttribArray(fill_data.color_attribute);
-
-        gl.bindBuffer(gl.ELEMENT_ARRAY_BUFFER, triangles.index_buffer);
         for (let x = 0; x <= 1; x++) {
             gl.uniform2fv(fill_data.translate_uniform, translate);
-            gl.drawElements(gl.TRIANGLES, triangulated.triangles.length, gl.UNSIGNED_INT, 0);
+            this.drawFill(gl, fill_data, triangles, triangulated);
             translate[0] += 2 * Math.PI;
         }
 
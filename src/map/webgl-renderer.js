@@ -4,6 +4,8 @@ import FillVertexShader from './shaders/fill-vertex-shader.glsl';
 import StrokeFragmentShader from './shaders/stroke-fragment-shader.glsl';
 import StrokeVertexShader from './shaders/stroke-vertex-shader.glsl';
 
+import { RenderBuffer } from '../../pkg/index';
+
 export default class WebGLRenderer {
     project() {
         return true;
@@ -94,7 +96,11 @@ export default class WebGLRenderer {
     }
 
     initForContext(canvas, gl, locations, triangulated) {
-        gl.getExtension("OES_element_index_uint");
+        // Without 32 bit indices the mesh is drawn in chunks with 16 bit indices
+        const uint_indices = gl.getExtension("OES_element_index_uint") !== null;
+        if (!uint_indices) {
+            triangulated.generate_chunks();
+        }
 
         const fill_shader_program = this.createShaderProgram(gl, FillVertexShader, FillFragmentShader);
         const fill_position_attribute = gl.getAttribLocation(fill_shader_program, 'aVertexPosition');
@@ -121,19 +127,22 @@ export default class WebGLRenderer {
 
         const position_buffer = gl.createBuffer();
         gl.bindBuffer(gl.ARRAY_BUFFER, position_buffer);
-        gl.bufferData(gl.ARRAY_BUFFER, triangulated.vertex, gl.STATIC_DRAW);
+        gl.bufferData(gl.ARRAY_BUFFER, uint_indices ? triangulated.vertex : triangulated.chunk_vertex, gl.STATIC_DRAW);
         const color_buffer = gl.createBuffer();
         gl.bindBuffer(gl.ARRAY_BUFFER, color_buffer);
-        gl.bufferData(gl.ARRAY_BUFFER, triangulated.color, gl.STATIC_DRAW);
+        gl.bufferData(gl.ARRAY_BUFFER, uint_indices ? triangulated.color : triangulated.chunk_color, gl.STATIC_DRAW);
         const index_buffer = gl.createBuffer();
         gl.bindBuffer(gl.ELEMENT_ARRAY_BUFFER, index_buffer);
-        gl.bufferData(gl.ELEMENT_ARRAY_BUFFER, triangulated.triangles, gl.STATIC_DRAW);
+        gl.bufferData(
+            gl.ELEMENT_ARRAY_BUFFER, uint_indices ? triangulated.triangles : triangulated.chunk_triangles, gl.STATIC_DRAW
+        );
         const outline_position_buffer = gl.createBuffer();
         gl.bindBuffer(gl.ARRAY_BUFFER, outline_position_buffer);
         gl.bufferData(gl.ARRAY_BUFFER, triangulated.outline_triangles, gl.STATIC_DRAW);
         const outline_normal_buffer = gl.createBuffer();
         gl.bindBuffer(gl.ARRAY_BUFFER, outline_normal_buffer);
         gl.bufferData(gl.ARRAY_BUFFER, triangulated.outline_normals, gl.STATIC_DRAW);
+        triangulated.clear_dirty();
 
         this.webgl_data = {
             canvas: canvas,
@@ -158,11 +167,87 @@ export default class WebGLRenderer {
             },
             triangles: {
                 position_buffer, color_buffer, index_buffer, texture,
-                outline_position_buffer, outline_normal_buffer,
+                outline_position_buffer, outline_normal_buffer, uint_indices,
             }
         };
     }
 
+    // Uploads the part of `data` given by `dirty`, or all of it if the size of the buffer changed
+    uploadBuffer(gl, target, buffer, data, dirty) {
+        gl.bindBuffer(target, buffer);
+        if (gl.getBufferParameter(target, gl.BUFFER_SIZE) !== data.byteLength) {
+            gl.bufferData(target, data, gl.STATIC_DRAW);
+        } else if (dirty && dirty[0] < dirty[1]) {
+            gl.bufferSubData(target, dirty[0] * data.BYTES_PER_ELEMENT, data.subarray(dirty[0], dirty[1]));
+        }
+    }
+
+    updateBuffers(triangulated) {
+        if (!this.webgl_data?.context) {
+            // Everything is uploaded by initForContext
+            return;
+        }
+        const gl = this.webgl_data.context;
+        const triangles = this.webgl_data.triangles;
+        if (triangles.uint_indices) {
+            this.uploadBuffer(
+                gl, gl.ARRAY_BUFFER, triangles.position_buffer,
+                triangulated.vertex, triangulated.dirty_range(RenderBuffer.Vertex)
+            );
+            this.uploadBuffer(
+                gl, gl.ARRAY_BUFFER, triangles.color_buffer,
+                triangulated.color, triangulated.dirty_range(RenderBuffer.Color)
+            );
+            this.uploadBuffer(
+                gl, gl.ELEMENT_ARRAY_BUFFER, triangles.index_buffer,
+                triangulated.triangles, triangulated.dirty_range(RenderBuffer.Triangles)
+            );
+        } else {
+            // The chunks are split again from scratch, so they are uploaded completely
+            triangulated.generate_chunks();
+            gl.bindBuffer(gl.ARRAY_BUFFER, triangles.position_buffer);
+            gl.bufferData(gl.ARRAY_BUFFER, triangulated.chunk_vertex, gl.STATIC_DRAW);
+            gl.bindBuffer(gl.ARRAY_BUFFER, triangles.color_buffer);
+            gl.bufferData(gl.ARRAY_BUFFER, triangulated.chunk_color, gl.STATIC_DRAW);
+            gl.bindBuffer(gl.ELEMENT_ARRAY_BUFFER, triangles.index_buffer);
+            gl.bufferData(gl.ELEMENT_ARRAY_BUFFER, triangulated.chunk_triangles, gl.STATIC_DRAW);
+        }
+        const outlines = triangulated.dirty_range(RenderBuffer.Outlines);
+        this.uploadBuffer(
+            gl, gl.ARRAY_BUFFER, triangles.outline_position_buffer, triangulated.outline_triangles, outlines
+        );
+        this.uploadBuffer(
+            gl, gl.ARRAY_BUFFER, triangles.outline_normal_buffer, triangulated.outline_normals, outlines
+        );
+        triangulated.clear_dirty();
+    }
+
+    bindFillBuffers(gl, fill_data, triangles, vertex_offset) {
+        gl.bindBuffer(gl.ARRAY_BUFFER, triangles.position_buffer);
+        gl.vertexAttribPointer(fill_data.position_attribute, 2, gl.FLOAT, false, 0, 8 * vertex_offset);
+        gl.enableVertexAttribArray(fill_data.position_attribute);
+
+        gl.bindBuffer(gl.ARRAY_BUFFER, triangles.color_buffer);
+        gl.vertexAttribPointer(fill_data.color_attribute, 1, gl.FLOAT, false, 0, 4 * vertex_offset);
+        gl.enableVertexAttribArray(fill_data.color_attribute);
+
+        gl.bindBuffer(gl.ELEMENT_ARRAY_BUFFER, triangles.index_buffer);
+    }
+
+    drawFill(gl, fill_data, triangles, triangulated) {
+        if (triangles.uint_indices) {
+            this.bindFillBuffers(gl, fill_data, triangles, 0);
+            gl.drawElements(gl.TRIANGLES, triangulated.triangles.length, gl.UNSIGNED_INT, 0);
+        } else {
+            for (let i = 0; i < triangulated.count_chunks(); i++) {
+                const chunk = triangulated.get_chunk(i);
+                this.bindFillBuffers(gl, fill_data, triangles, chunk.vertex_offset);
+                gl.drawElements(gl.TRIANGLES, chunk.triangle_count, gl.UNSIGNED_SHORT, 2 * chunk.triangle_offset);
+                chunk.free();
+            }
+        }
+    }
+
     deinitResources(_locations, _triangulated) {
         if (this.webgl_data?.context) {
             const gl = this.webgl_data.context;
@@ -213,17 +298,7 @@ export default class WebGLRenderer {
         gl.uniform2fv(fill_data.translate_uniform, translate);
         gl.uniform2fv(fill_data.scale_uniform, scale);
         gl.uniform1i(fill_data.colors_uniform, 0);
-        
-        gl.bindBuffer(gl.ARRAY_BUFFER, triangles.position_buffer);
-        gl.vertexAttribPointer(fill_data.position_attribute, 2, gl.FLOAT, false, 0, 0);
-        gl.enableVertexAttribArray(fill_data.position_attribute);
-
-        gl.bindBuffer(gl.ARRAY_BUFFER, triangles.color_buffer);
-        gl.vertexAttribPointer(fill_data.color_attribute, 1, gl.FLOAT, false, 0, 0);
-        gl.enableVertexAttribArray(fill_data.color_attribute);
-
-        gl.bindBuffer(gl.ELEMENT_ARRAY_BUFFER, triangles.index_buffer);
-        gl.drawElements(gl.TRIANGLES, triangulated.triangles.length, gl.UNSIGNED_INT, 0);
+        this.drawFill(gl, fill_data, triangles, triangulated);
 
         // Draw stroke
         gl.useProgram(stroke_data.shader_program);
@@ -0,0 +1,278 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use js_sys::{Uint32Array, Float32Array};
+use wasm_bindgen::prelude::*;
+
+use crate::{LocationData, Point, EARTH_RADIUS};
+
+struct Segment {
+    a: Point, b: Point,
+    min: Point, max: Point,
+}
+
+// All boundary segments of a location, sorted by their minimum x coordinate so that
+// we can quickly find the segments that could be close to a given bounding box.
+struct Boundary {
+    segments: Vec<Segment>,
+    max_width: f32,
+    min: Point,
+    max: Point,
+}
+
+impl Boundary {
+    fn new(loc: &LocationData) -> Boundary {
+        let mut segments = Vec::new();
+        let mut max_width = 0.0f32;
+        for poly in loc.polygons.iter() {
+            for ring in poly.rings() {
+                let len = ring.len() / 2;
+                for i in 0..len {
+                    let j = (i + 1) % len;
+                    let a = [ring[2 * i], ring[2 * i + 1]];
+                    let b = [ring[2 * j], ring[2 * j + 1]];
+                    let min = [a[0].min(b[0]), a[1].min(b[1])];
+                    let max = [a[0].max(b[0]), a[1].max(b[1])];
+                    max_width = max_width.max(max[0] - min[0]);
+                    segments.push(Segment { a, b, min, max });
+                }
+            }
+        }
+        segments.sort_by(|a, b| a.min[0].partial_cmp(&b.min[0]).unwrap());
+        Boundary { segments, max_width, min: loc.min, max: loc.max }
+    }
+
+    fn query(&self, min: Point, max: Point) -> impl Iterator<Item = &Segment> {
+        let start = self.segments.partition_point(|s| s.min[0] < min[0] - self.max_width);
+        self.segments[start..].iter()
+            .take_while(move |s| s.min[0] <= max[0])
+            .filter(move |s| s.max[0] >= min[0] && s.min[1] <= max[1] && s.max[1] >= min[1])
+    }
+}
+
+// Returns the interval of parameters `u` in [0, 1] for which `p + u * (q - p)` is inside the
+// capsule of radius `r` around the segment from `a` to `b`. The capsule is convex, so the union
+// of the intervals for the two end disks and the center rectangle is again an interval.
+fn capsule_interval(p: Point, q: Point, a: Point, b: Point, r: f32) -> Option<(f32, f32)> {
+    fn disk_interval(p: Point, d: Point, c: Point, r: f32) -> Option<(f32, f32)> {
+        let f = [p[0] - c[0], p[1] - c[1]];
+        let qa = d[0] * d[0] + d[1] * d[1];
+        let qb = 2.0 * (d[0] * f[0] + d[1] * f[1]);
+        let qc = f[0] * f[0] + f[1] * f[1] - r * r;
+        let disc = qb * qb - 4.0 * qa * qc;
+        if disc < 0.0 {
+            None
+        } else {
+            let sqrt = disc.sqrt();
+            Some(((-qb - sqrt) / (2.0 * qa), (-qb + sqrt) / (2.0 * qa)))
+        }
+    }
+    fn clip(lo: &mut f32, hi: &mut f32, start: f32, delta: f32, min: f32, max: f32) {
+        if delta == 0.0 {
+            if start < min || start > max {
+                *lo = f32::INFINITY;
+            }
+        } else {
+            let t0 = (min - start) / delta;
+            let t1 = (max - start) / delta;
+            *lo = lo.max(t0.min(t1));
+            *hi = hi.min(t0.max(t1));
+        }
+    }
+    let d = [q[0] - p[0], q[1] - p[1]];
+    if d[0] == 0.0 && d[1] == 0.0 {
+        return None;
+    }
+    let mut parts = [disk_interval(p, d, a, r), disk_interval(p, d, b, r), None];
+    let e = [b[0] - a[0], b[1] - a[1]];
+    let len = (e[0] * e[0] + e[1] * e[1]).sqrt();
+    if len != 0.0 {
+        let dir = [e[0] / len, e[1] / len];
+        let f = [p[0] - a[0], p[1] - a[1]];
+        let (mut lo, mut hi) = (f32::NEG_INFINITY, f32::INFINITY);
+        clip(&mut lo, &mut hi, f[0] * dir[0] + f[1] * dir[1], d[0] * dir[0] + d[1] * dir[1], 0.0, len);
+        clip(&mut lo, &mut hi, f[1] * dir[0] - f[0] * dir[1], d[1] * dir[0] - d[0] * dir[1], -r, r);
+        parts[2] = Some((lo, hi));
+    }
+    let mut res: Option<(f32, f32)> = None;
+    for (lo, hi) in parts.iter().flatten() {
+        let (lo, hi) = (lo.max(0.0), hi.min(1.0));
+        if lo <= hi {
+            res = Some(match res {
+                Some((rlo, rhi)) => (rlo.min(lo), rhi.max(hi)),
+                None => (lo, hi),
+            });
+        }
+    }
+    res
+}
+
+// Length of the part of the boundary of `a` that is within `tol` of the boundary of `b`. The
+// x coordinates are scaled by `kx` to get an approximately equidistant local frame.
+fn covered_length(a: &Boundary, b: &Boundary, kx: f32, tol: f32) -> f32 {
+    let tol_x = tol / kx;
+    let mut length = 0.0;
+    let mut intervals = Vec::new();
+    let min = [b.min[0] - tol_x, b.min[1] - tol];
+    let max = [b.max[0] + tol_x, b.max[1] + tol];
+    for s in a.query(min, max) {
+        let p = [s.a[0] * kx, s.a[1]];
+        let q = [s.b[0] * kx, s.b[1]];
+        intervals.clear();
+        let min = [s.min[0] - tol_x, s.min[1] - tol];
+        let max = [s.max[0] + tol_x, s.max[1] + tol];
+        for t in b.query(min, max) {
+            if let Some(int) = capsule_interval(p, q, [t.a[0] * kx, t.a[1]], [t.b[0] * kx, t.b[1]], tol) {
+                intervals.push(int);
+            }
+        }
+        if !intervals.is_empty() {
+            intervals.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            let mut covered = 0.0;
+            let mut cur = intervals[0];
+            for &(lo, hi) in &intervals[1..] {
+                if lo > cur.1 {
+                    covered += cur.1 - cur.0;
+                    cur = (lo, hi);
+                } else {
+                    cur.1 = cur.1.max(hi);
+                }
+            }
+            covered += cur.1 - cur.0;
+            length += covered * f32::hypot(q[0] - p[0], q[1] - p[1]);
+        }
+    }
+    length
+}
+
+#[wasm_bindgen]
+pub struct AdjacencyGraph {
+    count: usize,
+    edges: Vec<u32>,
+    lengths: Vec<f32>,
+}
+
+impl AdjacencyGraph {
+    /// Computes which of the given locations share a border. Two boundaries are considered
+    /// shared where they are closer than `tolerance` kilometres to each other, which allows
+    /// for the small gaps and overlaps introduced by simplification. The length of the shared
+    /// border is also given in kilometres.
+    pub fn compute(locs: &[&LocationData], tolerance: f32) -> AdjacencyGraph {
+        let tol = tolerance / EARTH_RADIUS;
+        let boundaries: Vec<Boundary> = locs.iter().map(|loc| Boundary::new(loc)).collect();
+        let margins: Vec<f32> = locs.iter().map(|loc| {
+            let lat = f32::max(loc.min[1].abs(), loc.max[1].abs()).min(1.55);
+            tol / lat.cos()
+        }).collect();
+        let max_margin = margins.iter().cloned().fold(0.0, f32::max);
+        let mut order: Vec<usize> = (0..locs.len()).collect();
+        order.sort_by(|&a, &b| locs[a].min[0].partial_cmp(&locs[b].min[0]).unwrap());
+        let mut pairs = Vec::new();
+        for (i, &a) in order.iter().enumerate() {
+            for &b in &order[i + 1..] {
+                if locs[b].min[0] > locs[a].max[0] + 2.0 * max_margin {
+                    break;
+                }
+                if locs[b].min[0] - margins[b] <= locs[a].max[0] + margins[a]
+                    && locs[b].min[1] - tol <= locs[a].max[1] + tol && locs[b].max[1] + tol >= locs[a].min[1] - tol {
+                    pairs.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+        pairs.sort();
+        let mut graph = AdjacencyGraph { count: locs.len(), edges: Vec::new(), lengths: Vec::new() };
+        for (a, b) in pairs {
+            let lat = (locs[a].min[1] + locs[a].max[1] + locs[b].min[1] + locs[b].max[1]) / 4.0;
+            let kx = lat.cos().max(0.02);
+            let length = (covered_length(&boundaries[a], &boundaries[b], kx, tol)
+                + covered_length(&boundaries[b], &boundaries[a], kx, tol)) / 2.0;
+            if length > 0.0 {
+                graph.edges.push(a as u32);
+                graph.edges.push(b as u32);
+                graph.lengths.push(length * EARTH_RADIUS);
+            }
+        }
+        graph
+    }
+
+    /// Assigns a palette index to every location, such that no two neighbours sharing a border
+    /// of at least `min_length` kilometres get the same index. Uses the DSATUR heuristic. If the
+    /// palette is too small, the index with the shortest border to equally coloured neighbours
+    /// is chosen instead.
+    pub fn color_locations(&self, palette_size: usize, min_length: f32) -> Vec<u32> {
+        let palette_size = palette_size.max(1);
+        let mut adjacent = vec![Vec::new(); self.count];
+        for (a, b, length) in self.iter() {
+            if length >= min_length {
+                adjacent[a].push((b, length));
+                adjacent[b].push((a, length));
+            }
+        }
+        // For every location the length of the border shared with each color
+        let mut conflicts = vec![vec![0.0f32; palette_size]; self.count];
+        let mut saturation = vec![0; self.count];
+        let mut colors = vec![u32::MAX; self.count];
+        let mut queue: BinaryHeap<_> = (0..self.count)
+            .map(|i| (0, adjacent[i].len(), Reverse(i)))
+            .collect();
+        while let Some((sat, _, Reverse(i))) = queue.pop() {
+            if colors[i] != u32::MAX || sat != saturation[i] {
+                continue;
+            }
+            let color = match conflicts[i].iter().position(|&c| c == 0.0) {
+                Some(color) => color,
+                None => (0..palette_size)
+                    .min_by(|&a, &b| conflicts[i][a].partial_cmp(&conflicts[i][b]).unwrap())
+                    .unwrap(),
+            };
+            colors[i] = color as u32;
+            for &(n, length) in &adjacent[i] {
+                if colors[n] == u32::MAX {
+                    if conflicts[n][color] == 0.0 {
+                        saturation[n] += 1;
+                        let degree = adjacent[n].iter().filter(|&&(m, _)| colors[m] == u32::MAX).count();
+                        queue.push((saturation[n], degree, Reverse(n)));
+                    }
+                    conflicts[n][color] += length;
+                }
+            }
+        }
+        colors
+    }
+
+    /// Iterates over all edges of the graph as `(first, second, length)`.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, f32)> + '_ {
+        self.lengths.iter().enumerate()
+            .map(move |(i, &len)| (self.edges[2 * i] as usize, self.edges[2 * i + 1] as usize, len))
+    }
+}
+
+#[wasm_bindgen]
+impl AdjacencyGraph {
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn edges(&self) -> Uint32Array {
+        unsafe { Uint32Array::view(&self.edges) }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn lengths(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.lengths) }
+    }
+
+    #[wasm_bindgen]
+    pub fn color(&self, palette_size: usize, min_length: f32) -> Vec<u32> {
+        self.color_locations(palette_size, min_length)
+    }
+
+    #[wasm_bindgen]
+    pub fn neighbours(&self, i: usize) -> Vec<usize> {
+        self.iter()
+            .filter_map(|(a, b, _)| if a == i { Some(b) } else if b == i { Some(a) } else { None })
+            .collect()
+    }
+}
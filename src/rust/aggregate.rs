@@ -0,0 +1,61 @@
+use std::f32::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use crate::spatial::RTree;
+use crate::LocationData;
+
+/// Per location statistics of a set of weighted points.
+#[wasm_bindgen]
+pub struct PointAggregation {
+    counts: Vec<u32>,
+    sums: Vec<f32>,
+}
+
+impl PointAggregation {
+    /// Assigns every point to all locations containing it. Coordinates are given in degrees as
+    /// a flat list of longitude and latitude pairs. If `weights` does not contain one weight per
+    /// point, e.g. because it is empty, every point has a weight of one.
+    pub fn compute(locs: &[&LocationData], cords: &[f32], weights: &[f32]) -> PointAggregation {
+        let weighted = weights.len() == cords.len() / 2;
+        let bounds: Vec<_> = locs.iter().map(|loc| (loc.min, loc.max)).collect();
+        let tree = RTree::new(&bounds);
+        let edge_index: Vec<Vec<_>> = locs.iter()
+            .map(|loc| loc.polygons.iter().map(|poly| poly.build_edge_index()).collect())
+            .collect();
+        let mut aggregation = PointAggregation { counts: vec![0; locs.len()], sums: vec![0.0; locs.len()] };
+        for (i, pos) in cords.chunks_exact(2).enumerate() {
+            let pos = [pos[0] * PI / 180.0, pos[1] * PI / 180.0];
+            let weight = if weighted { weights[i] } else { 1.0 };
+            tree.search(pos, pos, |l| {
+                let mut polys = locs[l].polygons.iter().zip(&edge_index[l]);
+                if polys.any(|(poly, index)| poly.contains(pos, index.as_ref())) {
+                    aggregation.counts[l] += 1;
+                    aggregation.sums[l] += weight;
+                }
+            });
+        }
+        aggregation
+    }
+}
+
+#[wasm_bindgen]
+impl PointAggregation {
+    #[wasm_bindgen(getter)]
+    pub fn counts(&self) -> Vec<u32> {
+        self.counts.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sums(&self) -> Vec<f32> {
+        self.sums.clone()
+    }
+
+    /// The mean weight of the points in each location, or `NaN` for locations without points.
+    #[wasm_bindgen(getter)]
+    pub fn means(&self) -> Vec<f32> {
+        self.counts.iter().zip(&self.sums)
+            .map(|(&count, &sum)| if count == 0 { f32::NAN } else { sum / count as f32 })
+            .collect()
+    }
+}
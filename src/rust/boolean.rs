@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use crate::Polygon;
+
+type Point = [f64; 2];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Operation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl Operation {
+    fn apply(self, inside: &[bool]) -> bool {
+        match self {
+            Operation::Union => inside.iter().any(|&i| i),
+            Operation::Intersection => inside.iter().all(|&i| i),
+            Operation::Difference => inside[0] && !inside[1..].iter().any(|&i| i),
+        }
+    }
+}
+
+// A single operand, i.e. a list of polygons each given by its outline followed by its holes.
+struct Shape {
+    polygons: Vec<Vec<Vec<Point>>>,
+    min: Point,
+    max: Point,
+}
+
+impl Shape {
+    fn new(polys: &[Polygon]) -> Shape {
+        let mut shape = Shape { polygons: Vec::new(), min: [0.0, 0.0], max: [0.0, 0.0] };
+        for poly in polys {
+            let rings: Vec<Vec<Point>> = poly.rings().map(|ring| {
+                ring.chunks_exact(2).map(|c| [c[0] as f64, c[1] as f64]).collect()
+            }).collect();
+            // Rings with less than three points enclose nothing, a polygon without an outline is
+            // skipped entirely
+            if rings.first().is_some_and(|outline| outline.len() >= 3) {
+                shape.polygons.push(rings.into_iter().filter(|ring| ring.len() >= 3).collect());
+            }
+        }
+        shape.update_bounds();
+        shape
+    }
+
+    fn update_bounds(&mut self) {
+        self.min = [f64::MAX, f64::MAX];
+        self.max = [f64::MIN, f64::MIN];
+        for p in self.polygons.iter().flatten().flatten() {
+            self.min = [self.min[0].min(p[0]), self.min[1].min(p[1])];
+            self.max = [self.max[0].max(p[0]), self.max[1].max(p[1])];
+        }
+    }
+
+    fn contains(&self, p: Point) -> bool {
+        if p[0] < self.min[0] || p[1] < self.min[1] || p[0] > self.max[0] || p[1] > self.max[1] {
+            return false;
+        }
+        self.polygons.iter().any(|rings| {
+            rings.iter().filter(|ring| ring_crossings(ring, p)).count() % 2 == 1
+        })
+    }
+}
+
+// Returns true if a ray from `p` towards positive x crosses the ring an odd number of times.
+fn ring_crossings(ring: &[Point], p: Point) -> bool {
+    let mut inside = false;
+    let mut last = ring[ring.len() - 1];
+    for &cur in ring {
+        if (cur[1] > p[1]) != (last[1] > p[1])
+            && p[0] < (last[0] - cur[0]) * (p[1] - cur[1]) / (last[1] - cur[1]) + cur[0]
+        {
+            inside = !inside;
+        }
+        last = cur;
+    }
+    inside
+}
+
+fn cross(a: Point, b: Point) -> f64 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn ring_area(ring: &[Point]) -> f64 {
+    let mut last = ring[ring.len() - 1];
+    let mut sum = 0.0;
+    for &cur in ring {
+        sum += cross(last, cur);
+        last = cur;
+    }
+    sum / 2.0
+}
+
+fn ring_perimeter(ring: &[Point]) -> f64 {
+    let mut last = ring[ring.len() - 1];
+    let mut sum = 0.0;
+    for &cur in ring {
+        sum += f64::hypot(cur[0] - last[0], cur[1] - last[1]);
+        last = cur;
+    }
+    sum
+}
+
+fn key(p: Point) -> [u64; 2] {
+    [p[0].to_bits(), p[1].to_bits()]
+}
+
+// Snaps all vertices that are within `tolerance` of an earlier vertex onto that vertex.
+fn snap_vertices(shapes: &mut [Shape], tolerance: f64) {
+    let mut grid: HashMap<(i64, i64), Vec<Point>> = HashMap::new();
+    for shape in shapes {
+        for ring in shape.polygons.iter_mut().flatten() {
+            for p in ring.iter_mut() {
+                let cell = ((p[0] / tolerance).floor() as i64, (p[1] / tolerance).floor() as i64);
+                let mut found = None;
+                'search: for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        if let Some(points) = grid.get(&(cell.0 + dx, cell.1 + dy)) {
+                            for &q in points {
+                                if f64::hypot(p[0] - q[0], p[1] - q[1]) <= tolerance {
+                                    found = Some(q);
+                                    break 'search;
+                                }
+                            }
+                        }
+                    }
+                }
+                match found {
+                    Some(q) => *p = q,
+                    None => grid.entry(cell).or_default().push(*p),
+                }
+            }
+        }
+        shape.update_bounds();
+    }
+}
+
+struct Edge {
+    a: Point,
+    b: Point,
+    splits: Vec<(f64, Point)>,
+}
+
+// Finds all intersections between the edges and records them as split points on both edges.
+fn split_edges(edges: &mut [Edge]) {
+    let mut order: Vec<usize> = (0..edges.len()).collect();
+    order.sort_by(|&i, &j| {
+        edges[i].a[0].min(edges[i].b[0]).partial_cmp(&edges[j].a[0].min(edges[j].b[0])).unwrap()
+    });
+    for (n, &i) in order.iter().enumerate() {
+        let (p1, p2) = (edges[i].a, edges[i].b);
+        let max_x = p1[0].max(p2[0]);
+        for &j in &order[n + 1..] {
+            let (q1, q2) = (edges[j].a, edges[j].b);
+            if q1[0].min(q2[0]) > max_x {
+                break;
+            }
+            if q1[1].max(q2[1]) < p1[1].min(p2[1]) || q1[1].min(q2[1]) > p1[1].max(p2[1]) {
+                continue;
+            }
+            let d = sub(p2, p1);
+            let e = sub(q2, q1);
+            let f = sub(q1, p1);
+            let denom = cross(d, e);
+            let len_d = d[0] * d[0] + d[1] * d[1];
+            let len_e = e[0] * e[0] + e[1] * e[1];
+            if denom.abs() > 1e-12 * (len_d * len_e).sqrt() {
+                let t = cross(f, e) / denom;
+                let u = cross(f, d) / denom;
+                if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+                    let x = if t == 0.0 {
+                        p1
+                    } else if t == 1.0 {
+                        p2
+                    } else if u == 0.0 {
+                        q1
+                    } else if u == 1.0 {
+                        q2
+                    } else {
+                        [p1[0] + t * d[0], p1[1] + t * d[1]]
+                    };
+                    edges[i].splits.push((t, x));
+                    edges[j].splits.push((u, x));
+                }
+            } else if cross(f, d).abs() <= 1e-12 * len_d {
+                // The edges are collinear, split both at the endpoints of the other one
+                for q in [q1, q2] {
+                    let t = (sub(q, p1)[0] * d[0] + sub(q, p1)[1] * d[1]) / len_d;
+                    if t > 0.0 && t < 1.0 {
+                        edges[i].splits.push((t, q));
+                    }
+                }
+                for p in [p1, p2] {
+                    let u = (sub(p, q1)[0] * e[0] + sub(p, q1)[1] * e[1]) / len_e;
+                    if u > 0.0 && u < 1.0 {
+                        edges[j].splits.push((u, p));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Links the directed edges into closed rings, always taking the leftmost turn so that rings
+// touching in a single vertex are separated.
+fn link_rings(edges: &[(Point, Point)]) -> Vec<Vec<Point>> {
+    let mut outgoing: HashMap<[u64; 2], Vec<usize>> = HashMap::new();
+    for (i, e) in edges.iter().enumerate() {
+        outgoing.entry(key(e.0)).or_default().push(i);
+    }
+    let mut used = vec![false; edges.len()];
+    let mut rings = Vec::new();
+    for start in 0..edges.len() {
+        if used[start] {
+            continue;
+        }
+        let mut ring = Vec::new();
+        let mut cur = start;
+        loop {
+            used[cur] = true;
+            ring.push(edges[cur].0);
+            let dir = sub(edges[cur].1, edges[cur].0);
+            let next = outgoing.get(&key(edges[cur].1)).into_iter().flatten()
+                .filter(|&&n| !used[n] || n == start)
+                .map(|&n| {
+                    let out = sub(edges[n].1, edges[n].0);
+                    (n, f64::atan2(cross(dir, out), dir[0] * out[0] + dir[1] * out[1]))
+                })
+                .filter(|&(_, angle)| angle < PI)
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            match next {
+                Some((n, _)) if n != start => cur = n,
+                _ => break,
+            }
+        }
+        if ring.len() >= 3 {
+            rings.push(ring);
+        }
+    }
+    rings
+}
+
+/// Computes the given boolean operation on the polygons of all the given shapes. Vertices closer
+/// than `tolerance` are snapped together and rings thinner than it are removed from the result.
+pub fn combine(shapes: &[&[Polygon]], tolerance: f32, op: Operation) -> Vec<Polygon> {
+    let tolerance = tolerance as f64;
+    let mut shapes: Vec<Shape> = shapes.iter().map(|polys| Shape::new(polys)).collect();
+    if tolerance > 0.0 {
+        snap_vertices(&mut shapes, tolerance);
+    }
+    let mut edges = Vec::new();
+    let mut extent = 0.0f64;
+    for shape in &shapes {
+        extent = extent.max(shape.max[0] - shape.min[0]).max(shape.max[1] - shape.min[1]);
+        for ring in shape.polygons.iter().flatten() {
+            let mut last = ring[ring.len() - 1];
+            for &cur in ring {
+                if cur != last {
+                    edges.push(Edge { a: last, b: cur, splits: Vec::new() });
+                }
+                last = cur;
+            }
+        }
+    }
+    split_edges(&mut edges);
+    let mut parts = HashMap::new();
+    for edge in &mut edges {
+        edge.splits.push((0.0, edge.a));
+        edge.splits.push((1.0, edge.b));
+        edge.splits.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        for w in edge.splits.windows(2) {
+            let (a, b) = (w[0].1, w[1].1);
+            if a != b {
+                let (ka, kb) = (key(a), key(b));
+                parts.entry(if ka < kb { (ka, kb) } else { (kb, ka) }).or_insert((a, b));
+            }
+        }
+    }
+    // Keep every edge that separates the inside from the outside of the result, oriented such
+    // that the inside is always on the left.
+    let eps = 1e-7 * extent;
+    let mut inside_left = vec![false; shapes.len()];
+    let mut inside_right = vec![false; shapes.len()];
+    let mut result = Vec::new();
+    for &(a, b) in parts.values() {
+        let d = sub(b, a);
+        let len = f64::hypot(d[0], d[1]);
+        let normal = [-d[1] / len * eps, d[0] / len * eps];
+        let mid = [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0];
+        let left = [mid[0] + normal[0], mid[1] + normal[1]];
+        let right = [mid[0] - normal[0], mid[1] - normal[1]];
+        for (i, shape) in shapes.iter().enumerate() {
+            inside_left[i] = shape.contains(left);
+            inside_right[i] = shape.contains(right);
+        }
+        match (op.apply(&inside_left), op.apply(&inside_right)) {
+            (true, false) => result.push((a, b)),
+            (false, true) => result.push((b, a)),
+            _ => { }
+        }
+    }
+    let mut outlines = Vec::new();
+    let mut holes = Vec::new();
+    for ring in link_rings(&result).into_iter().filter(|ring| ring.len() >= 3) {
+        let area = ring_area(&ring);
+        if 2.0 * area.abs() > tolerance * ring_perimeter(&ring) && area != 0.0 {
+            if area > 0.0 {
+                outlines.push((area, vec![ring]));
+            } else {
+                holes.push(ring);
+            }
+        }
+    }
+    // Every hole belongs to the smallest outline that contains it
+    outlines.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    for hole in holes {
+        let test = [(hole[0][0] + hole[1][0]) / 2.0, (hole[0][1] + hole[1][1]) / 2.0];
+        if let Some(outline) = outlines.iter_mut().find(|o| ring_crossings(&o.1[0], test)) {
+            outline.1.push(hole);
+        }
+    }
+    outlines.into_iter().map(|(_, rings)| {
+        let mut poly = Polygon {
+            vertex: Vec::new(), holes: Vec::new(),
+            min: [f32::MAX, f32::MAX], max: [f32::MIN, f32::MIN]
+        };
+        for (i, ring) in rings.iter().enumerate() {
+            if i != 0 {
+                poly.holes.push((poly.vertex.len() / 2) as u32);
+            }
+            for p in ring {
+                let (x, y) = (p[0] as f32, p[1] as f32);
+                poly.min = [poly.min[0].min(x), poly.min[1].min(y)];
+                poly.max = [poly.max[0].max(x), poly.max[1].max(y)];
+                poly.vertex.push(x);
+                poly.vertex.push(y);
+            }
+        }
+        poly
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polygon(rings: &[&[f32]]) -> Polygon {
+        let mut poly = Polygon {
+            vertex: Vec::new(), holes: Vec::new(),
+            min: [f32::MAX, f32::MAX], max: [f32::MIN, f32::MIN]
+        };
+        for (i, ring) in rings.iter().enumerate() {
+            if i != 0 {
+                poly.holes.push((poly.vertex.len() / 2) as u32);
+            }
+            for p in ring.chunks(2) {
+                poly.min = [poly.min[0].min(p[0]), poly.min[1].min(p[1])];
+                poly.max = [poly.max[0].max(p[0]), poly.max[1].max(p[1])];
+            }
+            poly.vertex.extend_from_slice(ring);
+        }
+        poly
+    }
+
+    fn area(polys: &[Polygon]) -> f64 {
+        polys.iter().flat_map(|poly| poly.rings())
+            .map(|ring| ring_area(&ring.chunks(2).map(|c| [c[0] as f64, c[1] as f64]).collect::<Vec<_>>()))
+            .sum()
+    }
+
+    #[test]
+    fn union_of_adjacent_squares() {
+        let a = [polygon(&[&[0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0]])];
+        let b = [polygon(&[&[1.0, 0.0, 2.0, 0.0, 2.0, 1.0, 1.0, 1.0]])];
+        let union = combine(&[&a, &b], 0.0, Operation::Union);
+        assert_eq!(union.len(), 1);
+        assert!((area(&union) - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn degenerate_rings_are_skipped() {
+        let a = [polygon(&[&[0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0], &[]]), polygon(&[&[]])];
+        let b = [polygon(&[&[2.0, 0.0, 3.0, 0.0]])];
+        let union = combine(&[&a, &b], 0.0, Operation::Union);
+        assert!((area(&union) - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,381 @@
+use std::f32::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use crate::spatial::RTree;
+use crate::{LocationData, Point, Polygon, Projection};
+
+// Number of vertices of the circles in a Dorling cartogram
+const CIRCLE_VERTICES: usize = 64;
+
+/// A cartogram, in which the projected area of every location is proportional to its value. The
+/// contiguous cartogram is computed with the rubber sheet algorithm of Dougenik et al., the
+/// projected geometry of all locations is distorted by the same continuous displacement field, so
+/// locations that share a border stay connected. The non-contiguous layouts replace every location
+/// with a circle or a copy of itself scaled around its label point.
+#[wasm_bindgen]
+pub struct Cartogram {
+    ids: Vec<String>,
+    names: Vec<String>,
+    polygons: Vec<Vec<Polygon>>,
+    error: f32,
+}
+
+/// Area, centroid and the change in size a location exerts on its surroundings.
+struct Force {
+    center: Point,
+    radius: f32,
+    mass: f32,
+}
+
+impl Cartogram {
+    /// Resizes the locations so that their projected area is proportional to the given values.
+    /// Locations with a value that is not finite keep their size.
+    pub fn compute(locs: &[&LocationData], values: &[f32], projection: &Projection, iterations: usize) -> Cartogram {
+        let mut polygons: Vec<Vec<Polygon>> = locs.iter()
+            .map(|loc| loc.projected(projection).polygons.to_vec())
+            .collect();
+        let mut error = size_error(&polygons, values);
+        for _ in 0..iterations {
+            let forces = forces(&polygons, values);
+            // Reduces the forces while the error is large, otherwise locations overshoot
+            let reduction = 1.0 / (1.0 + error);
+            for poly in polygons.iter_mut().flatten() {
+                for pos in poly.vertex.chunks_mut(2) {
+                    let mut delta = [0.0, 0.0];
+                    for force in &forces {
+                        let offset = [pos[0] - force.center[0], pos[1] - force.center[1]];
+                        let dist = f32::hypot(offset[0], offset[1]);
+                        if dist < 1e-12 {
+                            continue;
+                        }
+                        let ratio = dist / force.radius;
+                        let strength = if ratio > 1.0 {
+                            force.mass / ratio
+                        } else {
+                            force.mass * ratio * ratio * (4.0 - 3.0 * ratio)
+                        };
+                        delta[0] += reduction * strength * offset[0] / dist;
+                        delta[1] += reduction * strength * offset[1] / dist;
+                    }
+                    pos[0] += delta[0];
+                    pos[1] += delta[1];
+                }
+            }
+            error = size_error(&polygons, values);
+        }
+        let ids = locs.iter().map(|loc| loc.id.clone()).collect();
+        let names = locs.iter().map(|loc| loc.name.clone()).collect();
+        let polygons = locs.iter().zip(polygons)
+            .map(|(loc, polys)| {
+                let original = &loc.projected(projection).polygons;
+                original.iter().zip(&polys).filter_map(|(from, to)| unproject(from, to, projection)).collect()
+            })
+            .collect();
+        Cartogram { ids, names, polygons, error }
+    }
+
+    /// Scales every location around its label point, so that the location with the highest value
+    /// per area keeps its size. Locations with a value that is not finite keep their size and
+    /// locations without a positive value are removed.
+    pub fn compute_scaled(locs: &[&LocationData], values: &[f32], projection: &Projection) -> Cartogram {
+        let projections: Vec<_> = locs.iter().map(|loc| loc.projected(projection)).collect();
+        let projected: Vec<&[Polygon]> = projections.iter().map(|proj| &proj.polygons[..]).collect();
+        let areas = areas(&projected);
+        let density = areas.iter().zip(values)
+            .filter(|((area, _), value)| *area > 0.0 && value.is_finite())
+            .map(|(&(area, _), &value)| value / area)
+            .fold(0.0, f32::max);
+        let ids = locs.iter().map(|loc| loc.id.clone()).collect();
+        let names = locs.iter().map(|loc| loc.name.clone()).collect();
+        let polygons = projected.iter().zip(&areas).zip(values).map(|((polys, &(area, _)), &value)| {
+            let factor = if !value.is_finite() {
+                1.0
+            } else if value <= 0.0 || area <= 0.0 {
+                return Vec::new();
+            } else {
+                f32::sqrt(value / area / density)
+            };
+            let center = match label_point(polys) {
+                Some(center) => center,
+                None => return Vec::new(),
+            };
+            polys.iter().filter_map(|poly| {
+                let mut scaled = poly.clone();
+                for pos in scaled.vertex.chunks_mut(2) {
+                    pos[0] = center[0] + (pos[0] - center[0]) * factor;
+                    pos[1] = center[1] + (pos[1] - center[1]) * factor;
+                }
+                unproject(poly, &scaled, projection)
+            }).collect()
+        }).collect();
+        Cartogram { ids, names, polygons, error: 0.0 }
+    }
+
+    /// Replaces every location with a circle at its label point, with a total area equal to the
+    /// area of all locations. Overlapping circles are then pushed apart for the given number of
+    /// iterations, while being pulled back towards their label points less and less.
+    pub fn compute_dorling(locs: &[&LocationData], values: &[f32], projection: &Projection, iterations: usize) -> Cartogram {
+        let projections: Vec<_> = locs.iter().map(|loc| loc.projected(projection)).collect();
+        let projected: Vec<&[Polygon]> = projections.iter().map(|proj| &proj.polygons[..]).collect();
+        let areas = areas(&projected);
+        let desired = desired_areas(&areas, values);
+        let origins: Vec<Option<Point>> = projected.iter().zip(values)
+            .map(|(polys, value)| if *value > 0.0 || !value.is_finite() { label_point(polys) } else { None })
+            .collect();
+        let radii: Vec<f32> = desired.iter().zip(&origins)
+            .map(|(&area, origin)| if origin.is_some() { f32::sqrt(area / PI) } else { 0.0 })
+            .collect();
+        let mut centers: Vec<Point> = origins.iter().map(|origin| origin.unwrap_or([0.0, 0.0])).collect();
+        for k in 0..iterations {
+            // The pull fades out, so that the last iterations only remove overlaps
+            let pull = 0.1 * (1.0 - (k + 1) as f32 / iterations as f32);
+            let mut moves = vec![[0.0, 0.0]; centers.len()];
+            let tree = circle_tree(&centers, &radii);
+            for (i, &a) in centers.iter().enumerate() {
+                if radii[i] <= 0.0 {
+                    continue;
+                }
+                tree.search([a[0] - radii[i], a[1] - radii[i]], [a[0] + radii[i], a[1] + radii[i]], |j| {
+                    let b = centers[j];
+                    let dist = f32::hypot(b[0] - a[0], b[1] - a[1]);
+                    let overlap = radii[i] + radii[j] - dist;
+                    if i == j || radii[j] <= 0.0 || overlap <= 0.0 {
+                        return;
+                    }
+                    // Larger circles move less, circles at the same position are separated along x
+                    let share = radii[j] / (radii[i] + radii[j]);
+                    let dir = if dist > 1e-12 {
+                        [(a[0] - b[0]) / dist, (a[1] - b[1]) / dist]
+                    } else {
+                        [if i < j { -1.0 } else { 1.0 }, 0.0]
+                    };
+                    moves[i][0] += dir[0] * overlap * share;
+                    moves[i][1] += dir[1] * overlap * share;
+                });
+            }
+            for (i, center) in centers.iter_mut().enumerate() {
+                if let Some(origin) = origins[i] {
+                    center[0] += moves[i][0] + pull * (origin[0] - center[0]);
+                    center[1] += moves[i][1] + pull * (origin[1] - center[1]);
+                }
+            }
+        }
+        let ids = locs.iter().map(|loc| loc.id.clone()).collect();
+        let names = locs.iter().map(|loc| loc.name.clone()).collect();
+        let polygons = centers.iter().zip(&radii).map(|(&center, &radius)| {
+            if radius <= 0.0 {
+                return Vec::new();
+            }
+            let from = Polygon {
+                vertex: center.repeat(CIRCLE_VERTICES), holes: Vec::new(),
+                min: center, max: center,
+            };
+            let mut circle = from.clone();
+            for (k, pos) in circle.vertex.chunks_mut(2).enumerate() {
+                let angle = 2.0 * PI * k as f32 / CIRCLE_VERTICES as f32;
+                pos[0] += radius * angle.cos();
+                pos[1] -= radius * angle.sin();
+            }
+            unproject(&from, &circle, projection).into_iter().collect()
+        }).collect();
+        Cartogram { ids, names, polygons, error: overlap_error(&centers, &radii) }
+    }
+}
+
+#[wasm_bindgen]
+impl Cartogram {
+    #[wasm_bindgen]
+    pub fn count(&self) -> usize {
+        self.polygons.len()
+    }
+
+    /// The distorted geometry of the location at the given index, which can be used like the
+    /// original location. It has to be projected with the projection used for the cartogram.
+    #[wasm_bindgen]
+    pub fn get_location(&self, i: usize) -> LocationData {
+        LocationData::from_polygons(self.ids[i].clone(), self.names[i].clone(), self.polygons[i].clone())
+    }
+
+    /// For contiguous cartograms the mean relative difference between the projected areas and the
+    /// areas proportional to the values, for circles the mean overlap relative to their radius.
+    /// This is zero for a perfect layout.
+    #[wasm_bindgen(getter)]
+    pub fn error(&self) -> f32 {
+        self.error
+    }
+}
+
+fn ring_area(ring: &[f32]) -> (f32, Point) {
+    let len = ring.len() / 2;
+    let mut area = 0.0;
+    let mut center = [0.0, 0.0];
+    for i in 0..len {
+        let j = (i + 1) % len;
+        let (a, b) = ([ring[2 * i], ring[2 * i + 1]], [ring[2 * j], ring[2 * j + 1]]);
+        let cross = a[0] * b[1] - b[0] * a[1];
+        area += cross;
+        center[0] += (a[0] + b[0]) * cross;
+        center[1] += (a[1] + b[1]) * cross;
+    }
+    (area / 2.0, [center[0] / 6.0, center[1] / 6.0])
+}
+
+// Area and area weighted centroid of the polygons
+fn area(polys: &[Polygon]) -> (f32, Point) {
+    let mut area = 0.0;
+    let mut moment = [0.0, 0.0];
+    for poly in polys {
+        for (j, ring) in poly.rings().enumerate() {
+            let (ring_area, ring_moment) = ring_area(ring);
+            // Holes are oriented either way, they always reduce the area
+            let sign = if (j == 0) == (ring_area >= 0.0) { 1.0 } else { -1.0 };
+            area += sign * ring_area;
+            moment[0] += sign * ring_moment[0];
+            moment[1] += sign * ring_moment[1];
+        }
+    }
+    if area.abs() < 1e-12 {
+        (0.0, [0.0, 0.0])
+    } else {
+        (area.abs(), [moment[0] / area, moment[1] / area])
+    }
+}
+
+fn areas<P: AsRef<[Polygon]>>(polygons: &[P]) -> Vec<(f32, Point)> {
+    polygons.iter().map(|polys| area(polys.as_ref())).collect()
+}
+
+// A point inside of the largest polygon, its centroid if that is inside and otherwise the middle of
+// the widest horizontal span through the centroid
+fn label_point(polys: &[Polygon]) -> Option<Point> {
+    let (poly, (_, center)) = polys.iter()
+        .map(|poly| (poly, area(std::slice::from_ref(poly))))
+        .filter(|(_, (area, _))| *area > 0.0)
+        .max_by(|(_, a), (_, b)| a.0.total_cmp(&b.0))?;
+    if poly.contains(center, None) {
+        return Some(center);
+    }
+    let y = center[1];
+    let mut crossings = Vec::new();
+    for ring in poly.rings() {
+        let len = ring.len() / 2;
+        for i in 0..len {
+            let j = (i + 1) % len;
+            let (a, b) = ([ring[2 * i], ring[2 * i + 1]], [ring[2 * j], ring[2 * j + 1]]);
+            if (a[1] > y) != (b[1] > y) {
+                crossings.push(a[0] + (y - a[1]) / (b[1] - a[1]) * (b[0] - a[0]));
+            }
+        }
+    }
+    crossings.sort_by(f32::total_cmp);
+    crossings.chunks_exact(2)
+        .max_by(|a, b| (a[1] - a[0]).total_cmp(&(b[1] - b[0])))
+        .map(|span| [(span[0] + span[1]) / 2.0, y])
+}
+
+fn circle_tree(centers: &[Point], radii: &[f32]) -> RTree {
+    let boxes: Vec<(Point, Point)> = centers.iter().zip(radii)
+        .map(|(c, r)| ([c[0] - r, c[1] - r], [c[0] + r, c[1] + r]))
+        .collect();
+    RTree::new(&boxes)
+}
+
+fn overlap_error(centers: &[Point], radii: &[f32]) -> f32 {
+    let tree = circle_tree(centers, radii);
+    let mut errors = Vec::new();
+    for (i, &a) in centers.iter().enumerate() {
+        if radii[i] <= 0.0 {
+            continue;
+        }
+        let mut overlap: f32 = 0.0;
+        tree.search([a[0] - radii[i], a[1] - radii[i]], [a[0] + radii[i], a[1] + radii[i]], |j| {
+            if i != j && radii[j] > 0.0 {
+                let b = centers[j];
+                overlap = overlap.max(radii[i] + radii[j] - f32::hypot(b[0] - a[0], b[1] - a[1]));
+            }
+        });
+        errors.push(overlap.min(2.0 * radii[i]) / radii[i]);
+    }
+    if errors.is_empty() { 0.0 } else { errors.iter().sum::<f32>() / errors.len() as f32 }
+}
+
+// The desired areas, with the same total area as the current geometry
+fn desired_areas(areas: &[(f32, Point)], values: &[f32]) -> Vec<f32> {
+    let (mut total_area, mut total_value) = (0.0, 0.0);
+    for (&(area, _), &value) in areas.iter().zip(values) {
+        if value.is_finite() {
+            total_area += area;
+            total_value += value.max(0.0);
+        }
+    }
+    areas.iter().zip(values).map(|(&(area, _), &value)| {
+        if !value.is_finite() || total_value <= 0.0 {
+            area
+        } else {
+            // Locations are never shrunk to nothing, they would turn inside out
+            (total_area * value.max(0.0) / total_value).max(total_area * 1e-6)
+        }
+    }).collect()
+}
+
+fn forces(polygons: &[Vec<Polygon>], values: &[f32]) -> Vec<Force> {
+    let areas = areas(polygons);
+    let desired = desired_areas(&areas, values);
+    areas.iter().zip(desired)
+        .filter(|((area, _), _)| *area > 0.0)
+        .map(|(&(area, center), desired)| {
+            let radius = f32::sqrt(area / PI);
+            Force { center, radius, mass: f32::sqrt(desired / PI) - radius }
+        })
+        .collect()
+}
+
+fn size_error(polygons: &[Vec<Polygon>], values: &[f32]) -> f32 {
+    let areas = areas(polygons);
+    let desired = desired_areas(&areas, values);
+    let errors: Vec<_> = areas.iter().zip(desired)
+        .filter(|((area, _), _)| *area > 0.0)
+        .map(|(&(area, _), desired)| area.max(desired) / area.min(desired) - 1.0)
+        .collect();
+    if errors.is_empty() { 0.0 } else { errors.iter().sum::<f32>() / errors.len() as f32 }
+}
+
+// Maps the distorted polygon back to longitude and latitude. Vertices that were moved off the map
+// are moved back towards their original position until they can be inverted.
+fn unproject(from: &Polygon, to: &Polygon, projection: &Projection) -> Option<Polygon> {
+    let mut poly = Polygon {
+        vertex: Vec::new(), holes: Vec::new(),
+        min: [f32::MAX, f32::MAX], max: [f32::MIN, f32::MIN],
+    };
+    for (j, (from, to)) in from.rings().zip(to.rings()).enumerate() {
+        let start = poly.vertex.len();
+        for (a, b) in from.chunks(2).zip(to.chunks(2)) {
+            let mut t = 1.0;
+            let pos = loop {
+                let pos = [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])];
+                match projection.inverse(pos) {
+                    Some(pos) => break Some(pos),
+                    None if t > 0.0 => t = if t < 0.01 { 0.0 } else { t / 2.0 },
+                    None => break None,
+                }
+            };
+            if let Some(pos) = pos {
+                poly.vertex.extend(pos);
+            }
+        }
+        if poly.vertex.len() - start < 6 {
+            if j == 0 {
+                return None;
+            }
+            poly.vertex.truncate(start);
+        } else if j != 0 {
+            poly.holes.push((start / 2) as u32);
+        }
+    }
+    for pos in poly.vertex.chunks(2) {
+        poly.min = [poly.min[0].min(pos[0]), poly.min[1].min(pos[1])];
+        poly.max = [poly.max[0].max(pos[0]), poly.max[1].max(pos[1])];
+    }
+    Some(poly)
+}
@@ -0,0 +1,161 @@
+use std::ops::Range;
+
+use wasm_bindgen::prelude::*;
+
+// Number of vertices that can be indexed with 16 bit indices
+const MAX_VERTICES: usize = 1 << 16;
+
+/// A part of the mesh whose triangles only index up to 65 536 vertices, so that it can be drawn
+/// with 16 bit indices. Offsets and counts are given in vertices for the vertex and outline
+/// buffers and in indices for the triangles.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct Chunk {
+    vertex: Range<usize>,
+    triangles: Range<usize>,
+    outlines: Range<usize>,
+}
+
+#[wasm_bindgen]
+impl Chunk {
+    #[wasm_bindgen(getter)]
+    pub fn vertex_offset(&self) -> usize {
+        self.vertex.start
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn vertex_count(&self) -> usize {
+        self.vertex.len()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn triangle_offset(&self) -> usize {
+        self.triangles.start
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn outline_offset(&self) -> usize {
+        self.outlines.start
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn outline_count(&self) -> usize {
+        self.outlines.len()
+    }
+}
+
+/// A mesh split into chunks. Every chunk has its own copy of the vertices it uses and the indices
+/// of its triangles start at its first vertex. The triangles of every location are given as
+/// triples of chunk index, offset and count, because a location can span several chunks.
+#[derive(Default)]
+pub struct Chunks {
+    pub vertex: Vec<f32>,
+    pub color: Vec<f32>,
+    pub triangles: Vec<u16>,
+    pub chunks: Vec<Chunk>,
+    pub locations: Vec<Vec<usize>>,
+}
+
+impl Chunks {
+    /// Splits the triangles into chunks. `locations` contains the range of triangle indices and
+    /// of outline vertices of every location in order. The outlines of a location are drawn with
+    /// the chunk containing its last triangle.
+    pub fn split(vertex: &[f32], color: &[f32], triangles: &[u32], locations: &[(Range<usize>, Range<usize>)]) -> Chunks {
+        let mut chunks = Chunks::default();
+        // Index of every vertex in the current chunk
+        let mut local = vec![u32::MAX; vertex.len() / 2];
+        let mut used = Vec::new();
+        let mut chunk = Chunk::default();
+        for (tris, outlines) in locations {
+            let mut ranges = Vec::new();
+            let mut start = chunks.triangles.len();
+            for triangle in triangles[tris.clone()].chunks(3) {
+                let missing = triangle.iter().enumerate()
+                    .filter(|&(k, &v)| local[v as usize] == u32::MAX && !triangle[..k].contains(&v))
+                    .count();
+                if chunk.vertex.len() + missing > MAX_VERTICES {
+                    chunks.add_range(&mut ranges, start);
+                    start = chunks.triangles.len();
+                    let outlines = chunk.outlines.end;
+                    chunks.push(chunk);
+                    chunk = Chunk {
+                        vertex: chunks.color.len()..chunks.color.len(),
+                        triangles: chunks.triangles.len()..chunks.triangles.len(),
+                        outlines: outlines..outlines,
+                    };
+                    for v in used.drain(..) {
+                        local[v] = u32::MAX;
+                    }
+                }
+                for &v in triangle {
+                    let v = v as usize;
+                    if local[v] == u32::MAX {
+                        local[v] = chunk.vertex.len() as u32;
+                        used.push(v);
+                        chunks.vertex.extend(&vertex[2 * v..2 * v + 2]);
+                        chunks.color.push(color[v]);
+                        chunk.vertex.end += 1;
+                    }
+                    chunks.triangles.push(local[v] as u16);
+                }
+                chunk.triangles.end += 3;
+            }
+            chunks.add_range(&mut ranges, start);
+            chunks.locations.push(ranges);
+            chunk.outlines.end += outlines.len();
+        }
+        chunks.push(chunk);
+        chunks
+    }
+
+    // Adds the triangles from `start` on to the ranges of a location. They belong to the chunk
+    // that is pushed next.
+    fn add_range(&self, ranges: &mut Vec<usize>, start: usize) {
+        if self.triangles.len() > start {
+            ranges.extend([self.chunks.len(), start, self.triangles.len() - start]);
+        }
+    }
+
+    fn push(&mut self, chunk: Chunk) {
+        if !chunk.triangles.is_empty() || !chunk.outlines.is_empty() {
+            self.chunks.push(chunk);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_ranges_map_to_original_triangles() {
+        // Locations of separate triangles, together more than fit into one chunk
+        let (count, size) = (7, 4000);
+        let vertex: Vec<f32> = (0..2 * 3 * count * size).map(|c| c as f32).collect();
+        let color = vec![0.0; 3 * count * size];
+        let triangles: Vec<u32> = (0..(3 * count * size) as u32).collect();
+        let locations: Vec<_> = (0..count)
+            .map(|l| (3 * size * l..3 * size * (l + 1), 0..0))
+            .collect();
+        let chunks = Chunks::split(&vertex, &color, &triangles, &locations);
+        assert!(chunks.chunks.len() > 1);
+        for (l, (tris, _)) in locations.iter().enumerate() {
+            let mut original = Vec::new();
+            for range in chunks.locations[l].chunks(3) {
+                let chunk = &chunks.chunks[range[0]];
+                assert!(chunk.triangles.start <= range[1] && range[1] + range[2] <= chunk.triangles.end);
+                for &v in &chunks.triangles[range[1]..range[1] + range[2]] {
+                    let v = chunk.vertex.start + v as usize;
+                    original.push(&chunks.vertex[2 * v..2 * v + 2]);
+                }
+            }
+            let expected: Vec<_> = triangles[tris.clone()].iter().map(|&v| &vertex[2 * v as usize..2 * v as usize + 2]).collect();
+            assert_eq!(original, expected);
+        }
+    }
+}
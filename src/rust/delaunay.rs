@@ -0,0 +1,412 @@
+use std::collections::{HashMap, HashSet};
+
+use wasm_bindgen::prelude::*;
+
+use crate::Polygon;
+
+// Limits the number of Steiner points, because the refinement does not converge for every polygon
+const MAX_STEINER_POINTS: usize = 256;
+const MAX_STEINER_FACTOR: usize = 16;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TriangulationMode {
+    /// Ear clipping, which is fast but produces thin triangles.
+    Earcut,
+    /// Constrained Delaunay triangulation, which maximizes the smallest angle of the triangles.
+    Delaunay,
+}
+
+/// Limits for the refinement of a constrained Delaunay triangulation. Triangles with a longer edge
+/// or a smaller angle are split by inserting Steiner points. Zero disables the respective limit.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Refinement {
+    pub max_edge_length: f32,
+    /// The minimum angle in radians. Values above 30 degrees may not be reachable.
+    pub min_angle: f32,
+}
+
+/// A triangle mesh that finds triangles by their directed edges.
+struct Mesh {
+    points: Vec<[f64; 2]>,
+    triangles: Vec<[u32; 3]>,
+    edges: HashMap<(u32, u32), usize>,
+    constraints: HashSet<(u32, u32)>,
+}
+
+fn orient(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])
+}
+
+// Tests whether `d` lies inside of the circumcircle of the counterclockwise triangle `a`, `b`, `c`
+fn in_circle(a: [f64; 2], b: [f64; 2], c: [f64; 2], d: [f64; 2]) -> bool {
+    let [ax, ay] = [a[0] - d[0], a[1] - d[1]];
+    let [bx, by] = [b[0] - d[0], b[1] - d[1]];
+    let [cx, cy] = [c[0] - d[0], c[1] - d[1]];
+    let det = (ax * ax + ay * ay) * (bx * cy - cx * by)
+        - (bx * bx + by * by) * (ax * cy - cx * ay)
+        + (cx * cx + cy * cy) * (ax * by - bx * ay);
+    det > 0.0
+}
+
+fn undirected(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+impl Mesh {
+    fn new(vertex: &[f32], holes: &[u32], triangles: &[u32]) -> Mesh {
+        let mut mesh = Mesh {
+            points: vertex.chunks(2).map(|pos| [pos[0] as f64, pos[1] as f64]).collect(),
+            triangles: Vec::with_capacity(triangles.len() / 3),
+            edges: HashMap::new(),
+            constraints: HashSet::new(),
+        };
+        let len = (vertex.len() / 2) as u32;
+        let mut start = 0;
+        for &end in holes.iter().chain([len].iter()) {
+            for i in start..end {
+                let j = if i + 1 == end { start } else { i + 1 };
+                mesh.constraints.insert(undirected(i, j));
+            }
+            start = end;
+        }
+        for tri in triangles.chunks(3) {
+            let [a, b, c] = [tri[0], tri[1], tri[2]];
+            let tri = if mesh.orient(a, b, c) < 0.0 { [a, c, b] } else { [a, b, c] };
+            // Edges used by more than two triangles come from invalid input and are never flipped
+            for k in 0..3 {
+                if mesh.edges.contains_key(&(tri[k], tri[(k + 1) % 3])) {
+                    mesh.constraints.insert(undirected(tri[k], tri[(k + 1) % 3]));
+                }
+            }
+            mesh.triangles.push([0; 3]);
+            mesh.set(mesh.triangles.len() - 1, tri);
+        }
+        mesh
+    }
+
+    fn orient(&self, a: u32, b: u32, c: u32) -> f64 {
+        orient(self.points[a as usize], self.points[b as usize], self.points[c as usize])
+    }
+
+    fn set(&mut self, t: usize, tri: [u32; 3]) {
+        let old = self.triangles[t];
+        for k in 0..3 {
+            let edge = (old[k], old[(k + 1) % 3]);
+            if self.edges.get(&edge) == Some(&t) {
+                self.edges.remove(&edge);
+            }
+        }
+        self.triangles[t] = tri;
+        for k in 0..3 {
+            self.edges.insert((tri[k], tri[(k + 1) % 3]), t);
+        }
+    }
+
+    fn push(&mut self, tri: [u32; 3]) -> usize {
+        self.triangles.push([0; 3]);
+        self.set(self.triangles.len() - 1, tri);
+        self.triangles.len() - 1
+    }
+
+    // The triangle to the left of the directed edge and its third vertex
+    fn third(&self, a: u32, b: u32) -> Option<(usize, u32)> {
+        let &t = self.edges.get(&(a, b))?;
+        let tri = self.triangles[t];
+        let k = (0..3).find(|&k| tri[k] == a)?;
+        Some((t, tri[(k + 2) % 3]))
+    }
+
+    // Flips edges until all of them are locally Delaunay, starting with the given ones. Changed
+    // triangles are added to `touched`.
+    fn legalize(&mut self, mut stack: Vec<(u32, u32)>, touched: &mut Vec<usize>) {
+        while let Some((a, b)) = stack.pop() {
+            if self.constraints.contains(&undirected(a, b)) {
+                continue;
+            }
+            let (Some((t, c)), Some((u, d))) = (self.third(a, b), self.third(b, a)) else {
+                continue;
+            };
+            let [pa, pb, pc, pd] = [a, b, c, d].map(|i| self.points[i as usize]);
+            // Only convex quadrilaterals can be flipped, degenerate triangles are always flipped
+            if orient(pc, pa, pd) <= 0.0 || orient(pd, pb, pc) <= 0.0 {
+                continue;
+            }
+            if orient(pa, pb, pc) > 0.0 && !in_circle(pa, pb, pc, pd) {
+                continue;
+            }
+            self.set(t, [c, a, d]);
+            self.set(u, [d, b, c]);
+            touched.extend([t, u]);
+            stack.extend([(c, a), (a, d), (d, b), (b, c)]);
+        }
+    }
+
+    fn point(&self, i: u32) -> [f64; 2] {
+        self.points[i as usize]
+    }
+
+    fn add_point(&mut self, pos: [f64; 2]) -> u32 {
+        self.points.push(pos);
+        (self.points.len() - 1) as u32
+    }
+
+    // Inserts a point on the edge and splits the triangles on both sides
+    fn split_edge(&mut self, a: u32, b: u32, pos: [f64; 2], touched: &mut Vec<usize>) {
+        let m = self.add_point(pos);
+        if self.constraints.remove(&undirected(a, b)) {
+            self.constraints.insert(undirected(a, m));
+            self.constraints.insert(undirected(m, b));
+        }
+        let mut stack = Vec::new();
+        for (a, b) in [(a, b), (b, a)] {
+            if let Some((t, c)) = self.third(a, b) {
+                self.set(t, [a, m, c]);
+                let s = self.push([m, b, c]);
+                touched.extend([t, s]);
+                stack.extend([(c, a), (b, c)]);
+            }
+        }
+        self.legalize(stack, touched);
+    }
+
+    // Inserts a point inside of the triangle
+    fn split_triangle(&mut self, t: usize, pos: [f64; 2], touched: &mut Vec<usize>) {
+        let [a, b, c] = self.triangles[t];
+        let m = self.add_point(pos);
+        self.set(t, [a, b, m]);
+        let s = self.push([b, c, m]);
+        let u = self.push([c, a, m]);
+        touched.extend([t, s, u]);
+        self.legalize(vec![(a, b), (b, c), (c, a)], touched);
+    }
+
+    // Walks from the triangle towards the point. Returns the triangle containing the point, or the
+    // constraint that blocks the way, or nothing if the walk does not end.
+    fn locate(&self, mut t: usize, pos: [f64; 2]) -> Result<usize, Option<(u32, u32)>> {
+        for _ in 0..self.triangles.len() {
+            let tri = self.triangles[t];
+            let edge = (0..3)
+                .map(|k| (tri[k], tri[(k + 1) % 3]))
+                .find(|&(a, b)| orient(self.point(a), self.point(b), pos) < 0.0);
+            let Some((a, b)) = edge else {
+                return Ok(t);
+            };
+            if self.constraints.contains(&undirected(a, b)) {
+                return Err(Some((a, b)));
+            }
+            match self.third(b, a) {
+                Some((u, _)) => t = u,
+                None => return Err(Some((a, b))),
+            }
+        }
+        Err(None)
+    }
+
+    // A constraint of the triangle whose diametral circle contains the opposite vertex
+    fn encroached(&self, t: usize) -> Option<(u32, u32)> {
+        let tri = self.triangles[t];
+        (0..3).map(|k| (tri[k], tri[(k + 1) % 3], tri[(k + 2) % 3])).find_map(|(a, b, c)| {
+            let [pa, pb, pc] = [a, b, c].map(|i| self.point(i));
+            let dot = (pa[0] - pc[0]) * (pb[0] - pc[0]) + (pa[1] - pc[1]) * (pb[1] - pc[1]);
+            (dot < 0.0 && self.constraints.contains(&undirected(a, b))).then_some((a, b))
+        })
+    }
+
+    // A constraint of the triangle or its neighbours whose diametral circle contains the point
+    fn encroached_by(&self, t: usize, pos: [f64; 2]) -> Option<(u32, u32)> {
+        let tri = self.triangles[t];
+        let neighbours = (0..3).filter_map(|k| self.third(tri[(k + 1) % 3], tri[k]).map(|(u, _)| u));
+        [t].into_iter().chain(neighbours).find_map(|u| {
+            let tri = self.triangles[u];
+            (0..3).map(|k| (tri[k], tri[(k + 1) % 3])).find(|&(a, b)| {
+                let [pa, pb] = [self.point(a), self.point(b)];
+                let dot = (pa[0] - pos[0]) * (pb[0] - pos[0]) + (pa[1] - pos[1]) * (pb[1] - pos[1]);
+                dot < 0.0 && self.constraints.contains(&undirected(a, b))
+            })
+        })
+    }
+
+    fn is_bad(&self, t: usize, refinement: &Refinement, min_length: f64, original: u32) -> bool {
+        let tri = self.triangles[t];
+        let p = tri.map(|i| self.point(i));
+        let lengths = [0, 1, 2].map(|k| {
+            let (a, b) = (p[k], p[(k + 1) % 3]);
+            f64::hypot(b[0] - a[0], b[1] - a[1])
+        });
+        let longest = lengths.iter().copied().fold(0.0, f64::max);
+        if longest <= min_length {
+            return false;
+        }
+        if refinement.max_edge_length > 0.0 && longest > refinement.max_edge_length as f64 {
+            return true;
+        }
+        if refinement.min_angle > 0.0 {
+            // The smallest angle is opposite of the shortest edge
+            let shortest = (0..3).min_by(|&i, &j| lengths[i].total_cmp(&lengths[j])).unwrap_or(0);
+            let [e0, e1] = [(shortest + 1) % 3, (shortest + 2) % 3];
+            let sin = orient(p[0], p[1], p[2]).abs() / (lengths[e0] * lengths[e1]);
+            // Angles between two edges of the polygon can not be improved
+            let fixed = tri[e1] < original
+                && self.constraints.contains(&undirected(tri[e0], tri[(e0 + 1) % 3]))
+                && self.constraints.contains(&undirected(tri[e1], tri[(e1 + 1) % 3]));
+            return !fixed && sin < (refinement.min_angle as f64).sin();
+        }
+        false
+    }
+
+    fn circumcenter(&self, t: usize) -> Option<[f64; 2]> {
+        let [a, b, c] = self.triangles[t].map(|i| self.point(i));
+        let (b, c) = ([b[0] - a[0], b[1] - a[1]], [c[0] - a[0], c[1] - a[1]]);
+        let d = 2.0 * (b[0] * c[1] - b[1] * c[0]);
+        if d == 0.0 {
+            return None;
+        }
+        let (b2, c2) = (b[0] * b[0] + b[1] * b[1], c[0] * c[0] + c[1] * c[1]);
+        Some([a[0] + (c[1] * b2 - b[1] * c2) / d, a[1] + (b[0] * c2 - c[0] * b2) / d])
+    }
+
+    // Refines the triangulation with Ruppert's algorithm. Bad triangles get a new point at their
+    // circumcenter, unless that point lies beyond a constraint or inside of the diametral circle of
+    // one, which is then split in the middle instead.
+    fn refine(&mut self, refinement: &Refinement, min_length: f64, original: u32, max_points: usize) {
+        // Encroached constraints are split first
+        let mut queue: Vec<usize> = (0..self.triangles.len()).collect();
+        queue.sort_by_key(|&t| self.encroached(t).is_some());
+        while let Some(t) = queue.pop() {
+            if self.points.len() >= max_points {
+                break;
+            }
+            let length = |a: u32, b: u32| {
+                let (a, b) = (self.point(a), self.point(b));
+                f64::hypot(b[0] - a[0], b[1] - a[1])
+            };
+            if let Some((a, b)) = self.encroached(t) {
+                if length(a, b) > min_length {
+                    let (pa, pb) = (self.point(a), self.point(b));
+                    queue.push(t);
+                    self.split_edge(a, b, [(pa[0] + pb[0]) / 2.0, (pa[1] + pb[1]) / 2.0], &mut queue);
+                }
+                continue;
+            }
+            if !self.is_bad(t, refinement, min_length, original) {
+                continue;
+            }
+            let Some(center) = self.circumcenter(t) else {
+                continue;
+            };
+            let location = self.locate(t, center).and_then(|u| match self.encroached_by(u, center) {
+                Some(edge) => Err(Some(edge)),
+                None => Ok(u),
+            });
+            match location {
+                Ok(u) => {
+                    let tri = self.triangles[u];
+                    let edge = (0..3).map(|k| (tri[k], tri[(k + 1) % 3])).find(|&(a, b)| {
+                        orient(self.point(a), self.point(b), center).abs() <= 1e-12 * length(a, b).powi(2)
+                    });
+                    match edge {
+                        Some((a, b)) => self.split_edge(a, b, center, &mut queue),
+                        None => self.split_triangle(u, center, &mut queue),
+                    }
+                }
+                Err(Some((a, b))) if length(a, b) > min_length => {
+                    let (pa, pb) = (self.point(a), self.point(b));
+                    queue.push(t);
+                    self.split_edge(a, b, [(pa[0] + pb[0]) / 2.0, (pa[1] + pb[1]) / 2.0], &mut queue);
+                }
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+impl Polygon {
+    /// Replaces the triangles of the polygon starting at `start`, as produced by `triangulate_into`,
+    /// with its constrained Delaunay triangulation. Steiner points inserted by the refinement are
+    /// appended to `steiner` and indexed after the vertices of the polygon.
+    pub fn delaunay_into(&self, triangles: &mut Vec<u32>, start: usize, steiner: &mut Vec<f32>, refinement: &Refinement) {
+        let mut mesh = Mesh::new(&self.vertex, &self.holes, &triangles[start..]);
+        let mut touched = Vec::new();
+        // Sorted, so that the result does not depend on the order of the hash map
+        let mut stack: Vec<_> = mesh.edges.keys().copied().collect();
+        stack.sort_unstable();
+        mesh.legalize(stack, &mut touched);
+        if refinement.max_edge_length > 0.0 || refinement.min_angle > 0.0 {
+            let original = (self.vertex.len() / 2) as u32;
+            let max_points = self.vertex.len() / 2 * (MAX_STEINER_FACTOR + 1) + MAX_STEINER_POINTS;
+            let size = f32::max(self.max[0] - self.min[0], self.max[1] - self.min[1]) as f64;
+            mesh.refine(refinement, size * 1e-3, original, max_points);
+            for pos in &mesh.points[self.vertex.len() / 2..] {
+                steiner.extend([pos[0] as f32, pos[1] as f32]);
+            }
+        }
+        triangles.truncate(start);
+        triangles.extend(mesh.triangles.iter().flatten());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 10 by 2 rectangle with a square hole, which ear clipping splits into thin triangles
+    fn polygon() -> Polygon {
+        Polygon {
+            vertex: vec![0.0, 0.0, 10.0, 0.0, 10.0, 2.0, 0.0, 2.0, 4.0, 0.5, 4.0, 1.5, 5.0, 1.5, 5.0, 0.5],
+            holes: vec![4], min: [0.0, 0.0], max: [10.0, 2.0],
+        }
+    }
+
+    fn triangulate(poly: &Polygon, refinement: &Refinement) -> (Vec<[f64; 2]>, Vec<u32>) {
+        let mut triangles = Vec::new();
+        let mut steiner = Vec::new();
+        poly.triangulate_into(&mut triangles);
+        poly.delaunay_into(&mut triangles, 0, &mut steiner, refinement);
+        let points = poly.vertex.iter().chain(&steiner).copied().collect::<Vec<_>>()
+            .chunks(2).map(|p| [p[0] as f64, p[1] as f64]).collect();
+        (points, triangles)
+    }
+
+    fn area(points: &[[f64; 2]], triangles: &[u32]) -> f64 {
+        triangles.chunks(3).map(|t| orient(points[t[0] as usize], points[t[1] as usize], points[t[2] as usize]).abs() / 2.0).sum()
+    }
+
+    #[test]
+    fn preserves_area() {
+        let (points, triangles) = triangulate(&polygon(), &Refinement::default());
+        assert_eq!(points.len(), 8);
+        assert!((area(&points, &triangles) - 19.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn refined_mesh_passes_check() {
+        let poly = polygon();
+        let mut triangles = Vec::new();
+        let mut steiner = Vec::new();
+        poly.triangulate_into(&mut triangles);
+        let refinement = Refinement { max_edge_length: 1.0, min_angle: 25f32.to_radians() };
+        poly.delaunay_into(&mut triangles, 0, &mut steiner, &refinement);
+        assert!(!steiner.is_empty());
+        let result = crate::earcut::check(&poly.vertex, &poly.holes, &steiner, &triangles);
+        assert_eq!(result.status, crate::TriangulationStatus::Ok);
+        assert!(result.deviation < 1e-6);
+    }
+
+    #[test]
+    fn refinement_satisfies_min_angle() {
+        let min_angle = 25f32.to_radians();
+        let (points, triangles) = triangulate(&polygon(), &Refinement { max_edge_length: 0.0, min_angle });
+        assert!(points.len() > 8);
+        assert!((area(&points, &triangles) - 19.0).abs() < 1e-6);
+        for t in triangles.chunks(3) {
+            let p = [0, 1, 2].map(|k| points[t[k] as usize]);
+            for k in 0..3 {
+                let (a, b, c) = (p[k], p[(k + 1) % 3], p[(k + 2) % 3]);
+                let (u, v) = ([b[0] - a[0], b[1] - a[1]], [c[0] - a[0], c[1] - a[1]]);
+                let angle = f64::atan2((u[0] * v[1] - u[1] * v[0]).abs(), u[0] * v[0] + u[1] * v[1]);
+                assert!(angle >= min_angle as f64 - 1e-6, "angle {} in {:?}", angle.to_degrees(), p);
+            }
+        }
+    }
+}
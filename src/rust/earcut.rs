@@ -1,28 +1,81 @@
+use std::ops::{Add, Div, Mul, Sub};
+
+use wasm_bindgen::prelude::*;
+
+// Triangulations whose area differs more than this from the polygon area are inaccurate
+const MAX_DEVIATION: f32 = 1e-3;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum TriangulationStatus {
+    /// The triangles cover the polygon.
+    Ok,
+    /// The triangles cover too little or too much, usually because of self-intersecting rings.
+    Inaccurate,
+    /// There are no triangles, even though the polygon has an area.
+    Failed,
+}
+
+/// The quality of the triangulation of a polygon.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Triangulation {
+    pub status: TriangulationStatus,
+    /// The difference between the area of the triangles and the polygon relative to the polygon.
+    pub deviation: f32,
+}
+
+/// The floating point types that polygons can be triangulated in. Coordinates with a large
+/// magnitude need `f64` to avoid collapsed or flipped triangles.
+pub trait Float: Copy + PartialOrd + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> {
+    const ZERO: Self;
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn signum(self) -> Self;
+    fn is_nan(self) -> bool;
+}
+
+macro_rules! impl_float {
+    ($($t:ty),*) => {
+        $(impl Float for $t {
+            const ZERO: $t = 0.0;
+            fn from_f64(value: f64) -> $t { value as $t }
+            fn to_f64(self) -> f64 { self as f64 }
+            fn min(self, other: $t) -> $t { <$t>::min(self, other) }
+            fn max(self, other: $t) -> $t { <$t>::max(self, other) }
+            fn signum(self) -> $t { <$t>::signum(self) }
+            fn is_nan(self) -> bool { <$t>::is_nan(self) }
+        })*
+    };
+}
+
+impl_float!(f32, f64);
 
 #[derive(Clone)]
-struct Node {
-    i: usize, x: f32, y: f32,
+struct Node<T> {
+    i: usize, x: T, y: T,
     next: usize, prev: usize,
     z: u32, znext: usize, zprev: usize,
 }
 
-impl Node {
-    fn new(i: usize, x: f32, y: f32, next: usize, prev: usize) -> Node {
+impl<T: Float> Node<T> {
+    fn new(i: usize, x: T, y: T, next: usize, prev: usize) -> Node<T> {
         Node { i, x, y, next, prev, z: 0, znext: 0, zprev: 0 }
     }
 
-    fn dummy(x: f32, y: f32) -> Node {
+    fn dummy(x: T, y: T) -> Node<T> {
         Self::new(0, x, y, 0, 0)
     }
 }
 
-impl PartialEq for Node {
+impl<T: Float> PartialEq for Node<T> {
     fn eq(&self, other: &Self) -> bool {
         self.x == other.x && self.y == other.y
     }
 }
 
-pub fn triangulate_into(triangles: &mut Vec<u32>, vertex: &[f32], holes: &[u32], min: [f32; 2], max: [f32; 2]) {
+pub fn triangulate_into<T: Float>(triangles: &mut Vec<u32>, vertex: &[T], holes: &[u32], min: [T; 2], max: [T; 2]) {
     let node_len = vertex.len() / 2;
     let outer_len = if holes.len() > 0 { holes[0] as usize } else { node_len as usize };
     let mut nodes = Vec::with_capacity(node_len + 2 * holes.len());
@@ -37,32 +90,77 @@ pub fn triangulate_into(triangles: &mut Vec<u32>, vertex: &[f32], holes: &[u32],
         eliminate_holes(&mut nodes, holes, 0);
     }
     if node_len > 80 {
-        let mut inv_size = f32::max(max[0] - min[0], max[1] - min[1]);
-        inv_size = if inv_size != 0.0 { 1.0 / inv_size } else { 0.0 };
+        let mut inv_size = Float::max(max[0] - min[0], max[1] - min[1]);
+        inv_size = if inv_size != T::ZERO { T::from_f64(1.0) / inv_size } else { T::ZERO };
         generate_z_index(&mut nodes, 0, min, inv_size);
         apply_earcut(&mut nodes, 0, triangles, min, inv_size);
     } else {
-        apply_earcut(&mut nodes, 0, triangles, min, f32::NAN);
+        apply_earcut(&mut nodes, 0, triangles, min, T::from_f64(f64::NAN));
+    }
+}
+
+/// Compares the area of the triangles with the area of the polygon. The triangles index `vertex`
+/// followed by `steiner`, which holds points inserted into the polygon and is otherwise ignored.
+pub fn check<T: Float>(vertex: &[T], holes: &[u32], steiner: &[T], triangles: &[u32]) -> Triangulation {
+    fn ring_area<T: Float>(ring: &[T]) -> f64 {
+        let len = ring.len() / 2;
+        let mut sum = 0.0;
+        for i in 0..len {
+            let j = (i + len - 1) % len;
+            sum += (ring[2*j].to_f64() - ring[2*i].to_f64()) * (ring[2*i + 1].to_f64() + ring[2*j + 1].to_f64());
+        }
+        sum.abs() / 2.0
     }
+    let mut poly_area = 0.0;
+    let mut start = 0;
+    for (j, &end) in holes.iter().chain([(vertex.len() / 2) as u32].iter()).enumerate() {
+        let area = ring_area(&vertex[2 * start..2 * end as usize]);
+        poly_area += if j == 0 { area } else { -area };
+        start = end as usize;
+    }
+    let mut tri_area = 0.0;
+    let point = |i: u32| {
+        let i = 2 * i as usize;
+        let pos = if i < vertex.len() { &vertex[i..i + 2] } else { &steiner[i - vertex.len()..i - vertex.len() + 2] };
+        [pos[0].to_f64(), pos[1].to_f64()]
+    };
+    for tri in triangles.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| point(tri[k]));
+        tri_area += ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() / 2.0;
+    }
+    let deviation = if poly_area == 0.0 {
+        // Triangles of a polygon without area deviate completely
+        if tri_area == 0.0 { 0.0 } else { 1.0 }
+    } else {
+        ((tri_area - poly_area).abs() / poly_area.abs()) as f32
+    };
+    let status = if triangles.is_empty() && poly_area > 0.0 {
+        TriangulationStatus::Failed
+    } else if deviation > MAX_DEVIATION {
+        TriangulationStatus::Inaccurate
+    } else {
+        TriangulationStatus::Ok
+    };
+    Triangulation { status, deviation }
 }
 
-fn tri_area(a: &Node, b: &Node, c: &Node) -> f32 {
+fn tri_area<T: Float>(a: &Node<T>, b: &Node<T>, c: &Node<T>) -> T {
     (b.y - a.y) * (c.x - b.x) - (b.x - a.x) * (c.y - b.y)
 }
 
-fn point_in_triangle(a: &Node, b: &Node, c: &Node, p: &Node) -> bool {
+fn point_in_triangle<T: Float>(a: &Node<T>, b: &Node<T>, c: &Node<T>, p: &Node<T>) -> bool {
     let a0 = tri_area(p, c, a);
     let a1 = tri_area(p, a, b);
     let a2 = tri_area(p, b, c);
-    a0 <= 0.0 && a1 <= 0.0 && a2 <= 0.0
+    a0 <= T::ZERO && a1 <= T::ZERO && a2 <= T::ZERO
 }
 
-fn poly_area(nodes: &[Node], head: usize) -> f32 {
-    let mut sum = 0.0;
+fn poly_area<T: Float>(nodes: &[Node<T>], head: usize) -> T {
+    let mut sum = T::ZERO;
     let mut last = nodes[head].prev;
     let mut cur = head;
     loop {
-        sum += (nodes[last].x - nodes[cur].x) * (nodes[cur].y + nodes[last].y);
+        sum = sum + (nodes[last].x - nodes[cur].x) * (nodes[cur].y + nodes[last].y);
         last = cur;
         cur = nodes[cur].next;
         if cur == head {
@@ -72,24 +170,24 @@ fn poly_area(nodes: &[Node], head: usize) -> f32 {
     return sum;
 }
 
-fn lines_intersect(a0: &Node, b0: &Node, a1: &Node, b1: &Node) -> bool {
+fn lines_intersect<T: Float>(a0: &Node<T>, b0: &Node<T>, a1: &Node<T>, b1: &Node<T>) -> bool {
     let o1 = tri_area(a0, b0, a1).signum();
     let o2 = tri_area(a0, b0, b1).signum();
     let o3 = tri_area(a1, b1, a0).signum();
     let o4 = tri_area(a1, b1, b0).signum();
     o1 != o2 && o3 != o4
-        || (o1 == 0.0 && point_on_segment(a0, b0, a1))
-        || (o2 == 0.0 && point_on_segment(a0, b0, b1))
-        || (o3 == 0.0 && point_on_segment(a1, b1, a0))
-        || (o4 == 0.0 && point_on_segment(a1, b1, b0))
+        || (o1 == T::ZERO && point_on_segment(a0, b0, a1))
+        || (o2 == T::ZERO && point_on_segment(a0, b0, b1))
+        || (o3 == T::ZERO && point_on_segment(a1, b1, a0))
+        || (o4 == T::ZERO && point_on_segment(a1, b1, b0))
 }
 
-fn point_on_segment(a: &Node, b: &Node, p: &Node) -> bool {
-    p.x <= f32::max(a.x, b.x) && p.x >= f32::min(a.x, b.x)
-        && p.y <= f32::max(a.y, b.y) && p.y >= f32::min(a.y, b.y)
+fn point_on_segment<T: Float>(a: &Node<T>, b: &Node<T>, p: &Node<T>) -> bool {
+    p.x <= Float::max(a.x, b.x) && p.x >= Float::min(a.x, b.x)
+        && p.y <= Float::max(a.y, b.y) && p.y >= Float::min(a.y, b.y)
 }
 
-fn remove_node(nodes: &mut [Node], node: usize) {
+fn remove_node<T: Float>(nodes: &mut [Node<T>], node: usize) {
     let prev = nodes[node].prev;
     let next = nodes[node].next;
     nodes[prev].next = next;
@@ -104,10 +202,10 @@ fn remove_node(nodes: &mut [Node], node: usize) {
     }
 }
 
-fn create_list(nodes: &mut [Node], from: usize, to: usize, rev: bool) {
+fn create_list<T: Float>(nodes: &mut [Node<T>], from: usize, to: usize, rev: bool) {
     nodes[to - 1].next = from;
     nodes[from].prev = to - 1;
-    if rev == (poly_area(nodes, from) > 0.0) {
+    if rev == (poly_area(nodes, from) > T::ZERO) {
         let mut cur = from;
         loop {
             std::mem::swap(&mut nodes[cur].prev, &mut nodes[cur].next);
@@ -119,14 +217,14 @@ fn create_list(nodes: &mut [Node], from: usize, to: usize, rev: bool) {
     }
 }
 
-fn filter_points(nodes: &mut [Node], head: usize) -> usize {
+fn filter_points<T: Float>(nodes: &mut [Node<T>], head: usize) -> usize {
     let mut end = head;
     let mut cur = head;
     while cur != nodes[cur].next {
         let prev = nodes[cur].prev;
         let next = nodes[cur].next;
         if nodes[cur] == nodes[next]
-            || tri_area(&nodes[prev], &nodes[cur], &nodes[next]) == 0.0
+            || tri_area(&nodes[prev], &nodes[cur], &nodes[next]) == T::ZERO
         {
             remove_node(nodes, cur);
             cur = prev;
@@ -141,7 +239,7 @@ fn filter_points(nodes: &mut [Node], head: usize) -> usize {
     return cur;
 }
 
-fn find_leftmost(nodes: &[Node], head: usize) -> usize {
+fn find_leftmost<T: Float>(nodes: &[Node<T>], head: usize) -> usize {
     let mut min = head;
     let mut cur = head;
     loop {
@@ -156,7 +254,7 @@ fn find_leftmost(nodes: &[Node], head: usize) -> usize {
     return min;
 }
 
-fn eliminate_holes(nodes: &mut Vec<Node>, holes: &[u32], outer: usize) {
+fn eliminate_holes<T: Float>(nodes: &mut Vec<Node<T>>, holes: &[u32], outer: usize) {
     let mut queue = Vec::new();
     for i in 0..holes.len() {
         let start = holes[i] as usize;
@@ -170,14 +268,14 @@ fn eliminate_holes(nodes: &mut Vec<Node>, holes: &[u32], outer: usize) {
     }
 }
 
-fn eliminate_hole(nodes: &mut Vec<Node>, hole: usize, outer: usize) {
+fn eliminate_hole<T: Float>(nodes: &mut Vec<Node<T>>, hole: usize, outer: usize) {
     let bridge = find_bridge_point(nodes, hole, outer);
     create_bridge(nodes, bridge, hole);
 }
 
-fn find_bridge_point(nodes: &[Node], hole: usize, outer: usize) -> usize {
+fn find_bridge_point<T: Float>(nodes: &[Node<T>], hole: usize, outer: usize) -> usize {
     let mut cand = outer;
-    let mut cand_x = f32::NEG_INFINITY;
+    let mut cand_x = T::from_f64(f64::NEG_INFINITY);
     let mut cur = outer;
     loop {
         let next = nodes[cur].next;
@@ -211,7 +309,7 @@ fn find_bridge_point(nodes: &[Node], hole: usize, outer: usize) -> usize {
     return cand;
 }
 
-fn create_bridge(nodes: &mut Vec<Node>, a: usize, b: usize) -> usize {
+fn create_bridge<T: Float>(nodes: &mut Vec<Node<T>>, a: usize, b: usize) -> usize {
     let a2 = nodes.len();
     nodes.push(nodes[a].clone());
     let b2 = nodes.len();
@@ -229,7 +327,7 @@ fn create_bridge(nodes: &mut Vec<Node>, a: usize, b: usize) -> usize {
     return a2;
 }
 
-fn resolve_intersections(nodes: &mut [Node], head: usize, triangles: &mut Vec<u32>) -> usize {
+fn resolve_intersections<T: Float>(nodes: &mut [Node<T>], head: usize, triangles: &mut Vec<u32>) -> usize {
     let mut stop = head;
     let mut cur = head;
     while nodes[cur].prev != nodes[cur].next {
@@ -259,7 +357,7 @@ fn resolve_intersections(nodes: &mut [Node], head: usize, triangles: &mut Vec<u3
     return cur;
 }
 
-fn line_intersect_poly(nodes: &[Node], poly: usize, a: &Node, b: &Node) -> bool {
+fn line_intersect_poly<T: Float>(nodes: &[Node<T>], poly: usize, a: &Node<T>, b: &Node<T>) -> bool {
     let mut cur = poly;
     loop {
         let next = nodes[cur].next;
@@ -274,18 +372,18 @@ fn line_intersect_poly(nodes: &[Node], poly: usize, a: &Node, b: &Node) -> bool
     return false;
 }
 
-fn locally_inside(a0: &Node, a: &Node, a1: &Node, b: &Node) -> bool {
-    if tri_area(a0, a, a1) < 0.0 {
-        tri_area(a, b, a1) >= 0.0 && tri_area(a, a0, b) >= 0.0
+fn locally_inside<T: Float>(a0: &Node<T>, a: &Node<T>, a1: &Node<T>, b: &Node<T>) -> bool {
+    if tri_area(a0, a, a1) < T::ZERO {
+        tri_area(a, b, a1) >= T::ZERO && tri_area(a, a0, b) >= T::ZERO
     } else {
-        tri_area(a, b, a0) < 0.0 || tri_area(a, a1, b) < 0.0
+        tri_area(a, b, a0) < T::ZERO || tri_area(a, a1, b) < T::ZERO
     }
 }
 
-fn middle_inside(nodes: &[Node], a: usize, b: usize) -> bool {
+fn middle_inside<T: Float>(nodes: &[Node<T>], a: usize, b: usize) -> bool {
     let mut cur = a;
     let mut inside = false;
-    let middle = [(nodes[a].x + nodes[b].x) / 2.0, (nodes[a].y + nodes[b].y) / 2.0];
+    let middle = [(nodes[a].x + nodes[b].x) / T::from_f64(2.0), (nodes[a].y + nodes[b].y) / T::from_f64(2.0)];
     loop {
         let next = nodes[cur].next;
         if (nodes[cur].y > middle[1]) != (nodes[next].y > middle[1])
@@ -302,7 +400,7 @@ fn middle_inside(nodes: &[Node], a: usize, b: usize) -> bool {
     return inside;
 }
 
-fn is_possible_diagonal(nodes: &[Node], poly: usize, a: usize, b: usize) -> bool {
+fn is_possible_diagonal<T: Float>(nodes: &[Node<T>], poly: usize, a: usize, b: usize) -> bool {
     let a_next = nodes[a].next;
     let a_prev = nodes[a].prev;
     let b_next = nodes[b].next;
@@ -311,15 +409,15 @@ fn is_possible_diagonal(nodes: &[Node], poly: usize, a: usize, b: usize) -> bool
         && ((locally_inside(&nodes[a_prev], &nodes[a], &nodes[a_next], &nodes[b])
                 && locally_inside(&nodes[b_prev], &nodes[b], &nodes[b_next], &nodes[a])
                 && middle_inside(nodes, a, b) && (
-                    tri_area(&nodes[a_prev], &nodes[a], &nodes[b_prev]) != 0.0
-                    || tri_area(&nodes[a], &nodes[b_prev], &nodes[b]) != 0.0
+                    tri_area(&nodes[a_prev], &nodes[a], &nodes[b_prev]) != T::ZERO
+                    || tri_area(&nodes[a], &nodes[b_prev], &nodes[b]) != T::ZERO
                 ))
-            || (nodes[a] == nodes[b] && tri_area(&nodes[a_prev], &nodes[a], &nodes[a_next]) > 0.0
-                && tri_area(&nodes[b_prev], &nodes[b], &nodes[b_next]) > 0.0))
+            || (nodes[a] == nodes[b] && tri_area(&nodes[a_prev], &nodes[a], &nodes[a_next]) > T::ZERO
+                && tri_area(&nodes[b_prev], &nodes[b], &nodes[b_next]) > T::ZERO))
         && !line_intersect_poly(nodes, poly, &nodes[a], &nodes[b])
 }
 
-fn split_earcut(nodes: &mut Vec<Node>, head: usize, triangles: &mut Vec<u32>, min: [f32; 2], inv_size: f32) {
+fn split_earcut<T: Float>(nodes: &mut Vec<Node<T>>, head: usize, triangles: &mut Vec<u32>, min: [T; 2], inv_size: T) {
     let mut cur = head;
     loop {
         let next = nodes[cur].next;
@@ -349,7 +447,7 @@ fn split_earcut(nodes: &mut Vec<Node>, head: usize, triangles: &mut Vec<u32>, mi
     }
 }
 
-fn apply_earcut(nodes: &mut Vec<Node>, head: usize, triangles: &mut Vec<u32>, min: [f32; 2], inv_size: f32) {
+fn apply_earcut<T: Float>(nodes: &mut Vec<Node<T>>, head: usize, triangles: &mut Vec<u32>, min: [T; 2], inv_size: T) {
     let mut pass = 0;
     let mut cur = head;
     while nodes[cur].prev != nodes[cur].next && pass < 3 {
@@ -390,18 +488,18 @@ fn apply_earcut(nodes: &mut Vec<Node>, head: usize, triangles: &mut Vec<u32>, mi
     }
 }
 
-fn is_ear(nodes: &[Node], node: usize) -> bool {
+fn is_ear<T: Float>(nodes: &[Node<T>], node: usize) -> bool {
     let a = &nodes[nodes[node].prev];
     let b = &nodes[node];
     let c = &nodes[nodes[node].next];
-    if tri_area(a, b, c) >= 0.0 {
+    if tri_area(a, b, c) >= T::ZERO {
         return false;
     }
     let mut cur = nodes[nodes[node].next].next;
     loop {
         let prev = nodes[cur].prev;
         let next = nodes[cur].next;
-        if point_in_triangle(a, b, c, &nodes[cur]) && tri_area(&nodes[prev], &nodes[cur], &nodes[next]) >= 0.0 {
+        if point_in_triangle(a, b, c, &nodes[cur]) && tri_area(&nodes[prev], &nodes[cur], &nodes[next]) >= T::ZERO {
             return false;
         }
         cur = next;
@@ -412,7 +510,7 @@ fn is_ear(nodes: &[Node], node: usize) -> bool {
     return true;
 }
 
-fn generate_z_index(nodes: &mut [Node], outer: usize, min: [f32; 2], inv_size: f32) {
+fn generate_z_index<T: Float>(nodes: &mut [Node<T>], outer: usize, min: [T; 2], inv_size: T) {
     let mut ordered = Vec::with_capacity(nodes.len());
     let mut cur = outer;
     loop {
@@ -430,9 +528,9 @@ fn generate_z_index(nodes: &mut [Node], outer: usize, min: [f32; 2], inv_size: f
     }
 }
 
-fn z_order(pos: [f32; 2], min: [f32; 2], inv_size: f32) -> u32 {
-    let mut x = (32767.0 * (pos[0] - min[0]) * inv_size) as u32;
-    let mut y = (32767.0 * (pos[1] - min[1]) * inv_size) as u32;
+fn z_order<T: Float>(pos: [T; 2], min: [T; 2], inv_size: T) -> u32 {
+    let mut x = (T::from_f64(32767.0) * (pos[0] - min[0]) * inv_size).to_f64() as u32;
+    let mut y = (T::from_f64(32767.0) * (pos[1] - min[1]) * inv_size).to_f64() as u32;
     x = (x | (x << 8)) & 0x00FF00FF;
     x = (x | (x << 4)) & 0x0F0F0F0F;
     x = (x | (x << 2)) & 0x33333333;
@@ -444,13 +542,13 @@ fn z_order(pos: [f32; 2], min: [f32; 2], inv_size: f32) -> u32 {
     return x | (y << 1);
 }
 
-fn is_ear_z_index(nodes: &[Node], node: usize, min: [f32; 2], inv_size: f32) -> bool {
+fn is_ear_z_index<T: Float>(nodes: &[Node<T>], node: usize, min: [T; 2], inv_size: T) -> bool {
     let prev = nodes[node].prev;
     let next = nodes[node].next;
     let a = &nodes[prev];
     let b = &nodes[node];
     let c = &nodes[next];
-    if tri_area(a, b, c) >= 0.0 {
+    if tri_area(a, b, c) >= T::ZERO {
         return false;
     }
     let min_t = [a.x.min(b.x).min(c.x), a.y.min(b.y).min(c.y)];
@@ -464,7 +562,7 @@ fn is_ear_z_index(nodes: &[Node], node: usize, min: [f32; 2], inv_size: f32) ->
             if p != prev && p != next && point_in_triangle(a, b, c, &nodes[p]) {
                 let pprev = nodes[p].prev;
                 let pnext = nodes[p].next;
-                if tri_area(&nodes[pprev], &nodes[p], &nodes[pnext]) >= 0.0 {
+                if tri_area(&nodes[pprev], &nodes[p], &nodes[pnext]) >= T::ZERO {
                     return false;
                 }
             }
@@ -474,7 +572,7 @@ fn is_ear_z_index(nodes: &[Node], node: usize, min: [f32; 2], inv_size: f32) ->
             if n != prev && n != next && point_in_triangle(a, b, c, &nodes[n]) {
                 let nprev = nodes[n].prev;
                 let nnext = nodes[n].next;
-                if tri_area(&nodes[nprev], &nodes[n], &nodes[nnext]) >= 0.0 {
+                if tri_area(&nodes[nprev], &nodes[n], &nodes[nnext]) >= T::ZERO {
                     return false;
                 }
             }
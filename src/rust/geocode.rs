@@ -0,0 +1,114 @@
+use std::f32::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use crate::spatial::RTree;
+use crate::{read_signed, read_unsigned, LocationData, Point};
+
+/// Index over the bounding boxes of all locations of a dataset, used to find the locations a
+/// coordinate could fall into without having to load the geometry of every location. The
+/// geometry of the candidates is added on demand using `add_location`.
+#[wasm_bindgen]
+pub struct LocationIndex {
+    ids: Vec<String>,
+    levels: Vec<u32>,
+    tree: RTree,
+    locs: Vec<Option<LocationData>>,
+}
+
+impl LocationIndex {
+    fn candidates(&self, pos: Point, level: u32) -> Vec<usize> {
+        let mut candidates = Vec::new();
+        self.tree.search(pos, pos, |i| {
+            if self.levels[i] == level {
+                candidates.push(i);
+            }
+        });
+        candidates.sort();
+        candidates
+    }
+}
+
+#[wasm_bindgen]
+impl LocationIndex {
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> usize {
+        self.ids.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn get_id(&self, i: usize) -> String {
+        self.ids[i].clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn get_level(&self, i: usize) -> u32 {
+        self.levels[i]
+    }
+
+    #[wasm_bindgen]
+    pub fn parse_location_index(raw: &[u8]) -> LocationIndex {
+        let count = read_unsigned(&raw[0..4]) as usize;
+        let mut len = 4;
+        let mut index = LocationIndex {
+            ids: Vec::with_capacity(count), levels: Vec::with_capacity(count),
+            tree: RTree::new(&[]), locs: vec![None; count],
+        };
+        let mut bounds = Vec::with_capacity(count);
+        for _ in 0..count {
+            let start = len;
+            while raw[len] != 0 {
+                len += 1;
+            }
+            index.ids.push(std::str::from_utf8(&raw[start..len]).unwrap_or("").to_owned());
+            len += 1;
+            index.levels.push(read_unsigned(&raw[len..len + 4]));
+            len += 4;
+            let mut cords = [0.0; 4];
+            for cord in &mut cords {
+                *cord = read_signed(&raw[len..len + 4]) as f32 * PI / 180.0e7;
+                len += 4;
+            }
+            bounds.push(([cords[0], cords[1]], [cords[2], cords[3]]));
+        }
+        index.tree = RTree::new(&bounds);
+        index
+    }
+
+    /// Provides the geometry for the location at index `i`. The index keeps its own copy, so the
+    /// given handle can be freed afterwards.
+    #[wasm_bindgen]
+    pub fn add_location(&mut self, i: usize, loc: &LocationData) {
+        self.locs[i] = Some(loc.clone());
+    }
+
+    /// Returns the locations on the given level whose geometry is needed to resolve the given
+    /// coordinates, but has not yet been added. Coordinates are given in degrees as a flat list
+    /// of longitude and latitude pairs.
+    #[wasm_bindgen]
+    pub fn missing_locations(&self, cords: Vec<f32>, level: u32) -> Vec<usize> {
+        let mut missing = Vec::new();
+        for pos in cords.chunks(2) {
+            let pos = [pos[0] * PI / 180.0, pos[1] * PI / 180.0];
+            missing.extend(self.candidates(pos, level).into_iter().filter(|&i| self.locs[i].is_none()));
+        }
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+
+    /// Finds for each coordinate the location on the given level containing it. The result holds
+    /// the index of the location in this index, or -1 if none of the added locations contains
+    /// the coordinate.
+    #[wasm_bindgen]
+    pub fn resolve(&self, cords: Vec<f32>, level: u32) -> Vec<i32> {
+        cords.chunks(2).map(|pos| {
+            let pos = [pos[0] * PI / 180.0, pos[1] * PI / 180.0];
+            self.candidates(pos, level).into_iter()
+                .find(|&i| self.locs[i].as_ref()
+                    .is_some_and(|loc| loc.polygons.iter().any(|poly| poly.contains(pos, None))))
+                .map(|i| i as i32)
+                .unwrap_or(-1)
+        }).collect()
+    }
+}
@@ -0,0 +1,74 @@
+use std::f32::consts::PI;
+
+use crate::Polygon;
+
+// Below this sine of the angle between the end points of an edge they are considered antipodal
+const ANTIPODAL_EPSILON: f32 = 1e-4;
+
+fn to_vector(lon: f32, lat: f32) -> [f32; 3] {
+    [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+}
+
+impl Polygon {
+    /// Inserts points along the great circle of every edge that is longer than `max_angle`, given
+    /// in radians. Edges spanning more than half of the globe in longitude, such as the ones along
+    /// the poles, are kept as they are. The great circle between antipodal points is not unique,
+    /// so their edges are subdivided linearly in longitude and latitude instead.
+    pub fn densified(&self, max_angle: f32) -> Polygon {
+        let mut poly = Polygon { vertex: Vec::new(), holes: Vec::new(), min: self.min, max: self.max };
+        for (j, ring) in self.rings().enumerate() {
+            if j != 0 {
+                poly.holes.push((poly.vertex.len() / 2) as u32);
+            }
+            let len = ring.len() / 2;
+            for i in 0..len {
+                let k = (i + 1) % len;
+                let (a, b) = ([ring[2 * i], ring[2 * i + 1]], [ring[2 * k], ring[2 * k + 1]]);
+                poly.vertex.extend(a);
+                if (b[0] - a[0]).abs() >= PI {
+                    continue;
+                }
+                let (u, v) = (to_vector(a[0], a[1]), to_vector(b[0], b[1]));
+                let cross = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+                let dot = u[0] * v[0] + u[1] * v[1] + u[2] * v[2];
+                let angle = f32::atan2(f32::sqrt(cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2]), dot);
+                let steps = (angle / max_angle).ceil() as usize;
+                let antipodal = angle.sin() < ANTIPODAL_EPSILON;
+                for s in 1..steps {
+                    let t = s as f32 / steps as f32;
+                    let point = if antipodal {
+                        [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])]
+                    } else {
+                        let (wu, wv) = (f32::sin((1.0 - t) * angle), f32::sin(t * angle));
+                        let p = [0, 1, 2].map(|d| (wu * u[d] + wv * v[d]) / angle.sin());
+                        [f32::atan2(p[1], p[0]), f32::asin(p[2].clamp(-1.0, 1.0))]
+                    };
+                    poly.min = [poly.min[0].min(point[0]), poly.min[1].min(point[1])];
+                    poly.max = [poly.max[0].max(point[0]), poly.max[1].max(point[1])];
+                    poly.vertex.extend(point);
+                }
+            }
+        }
+        poly
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edges_between_antipodal_points_follow_the_meridian() {
+        // Triangle with an edge along a meridian from pole to pole
+        let poly = Polygon {
+            vertex: vec![0.0, -PI / 2.0, 0.5, -PI / 2.0, 0.5, PI / 2.0],
+            holes: Vec::new(), min: [0.0, -PI / 2.0], max: [0.5, PI / 2.0],
+        };
+        let densified = poly.densified(PI / 18.0);
+        // The points inserted between the poles follow the meridian of the edge
+        let meridian: Vec<&[f32]> = densified.vertex.chunks(2).skip(1).take_while(|p| p[1] < PI / 2.0).collect();
+        assert!(meridian.len() > 2);
+        assert!(meridian.iter().all(|p| p[0] == 0.5));
+        assert!(meridian.windows(2).all(|w| w[0][1] < w[1][1]));
+    }
+}
@@ -0,0 +1,110 @@
+use wasm_bindgen::prelude::*;
+
+use crate::spatial::Grid;
+use crate::{Point, Polygon};
+
+#[wasm_bindgen]
+pub struct Intersection {
+    location: usize,
+    polygon: usize,
+    distance: f32,
+}
+
+impl Intersection {
+    pub fn new(location: usize, polygon: usize, distance: f32) -> Intersection {
+        Intersection { location, polygon, distance }
+    }
+}
+
+#[wasm_bindgen]
+impl Intersection {
+    #[wasm_bindgen(getter)]
+    pub fn location(&self) -> usize {
+        self.location
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn polygon(&self) -> usize {
+        self.polygon
+    }
+
+    /// Distance to the location, this is zero if the location contains the point.
+    #[wasm_bindgen(getter)]
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+}
+
+impl Polygon {
+    fn ring_of(&self, i: usize) -> (usize, usize, usize) {
+        let r = self.holes.partition_point(|&h| h as usize <= i);
+        let start = if r == 0 { 0 } else { self.holes[r - 1] as usize };
+        let end = if r == self.holes.len() { self.vertex.len() / 2 } else { self.holes[r] as usize };
+        (r, start, end)
+    }
+
+    // Returns the ring the edge starting at vertex `i` belongs to and its end points.
+    fn edge(&self, i: usize) -> (usize, Point, Point) {
+        let (r, start, end) = self.ring_of(i);
+        let j = if i + 1 == end { start } else { i + 1 };
+        (r, [self.vertex[2 * i], self.vertex[2 * i + 1]], [self.vertex[2 * j], self.vertex[2 * j + 1]])
+    }
+
+    /// Builds an index of horizontal bands, each containing the edges crossing it. Returns `None`
+    /// if the polygon is small enough to simply test all edges.
+    pub fn build_edge_index(&self) -> Option<Grid> {
+        let len = self.vertex.len() / 2;
+        if len > 32 {
+            let boxes: Vec<(Point, Point)> = (0..len).map(|i| {
+                let (_, a, b) = self.edge(i);
+                ([a[0].min(b[0]), a[1].min(b[1])], [a[0].max(b[0]), a[1].max(b[1])])
+            }).collect();
+            let bands = (len as f32).sqrt().ceil() as usize;
+            Some(Grid::new(self.min, self.max, [1, bands], &boxes))
+        } else {
+            None
+        }
+    }
+
+    /// Tests whether the polygon contains the point using the winding number of each ring. The
+    /// point must be inside of the outline and outside of all holes.
+    pub fn contains(&self, pos: Point, index: Option<&Grid>) -> bool {
+        fn is_left(a: Point, b: Point, p: Point) -> f32 {
+            (b[0] - a[0]) * (p[1] - a[1]) - (p[0] - a[0]) * (b[1] - a[1])
+        }
+        if pos[0] < self.min[0] || pos[1] < self.min[1] || pos[0] > self.max[0] || pos[1] > self.max[1] {
+            return false;
+        }
+        let mut winding = vec![0; self.holes.len() + 1];
+        let mut test = |i: usize| {
+            let (r, a, b) = self.edge(i);
+            if a[1] <= pos[1] && b[1] > pos[1] && is_left(a, b, pos) > 0.0 {
+                winding[r] += 1;
+            } else if a[1] > pos[1] && b[1] <= pos[1] && is_left(a, b, pos) < 0.0 {
+                winding[r] -= 1;
+            }
+        };
+        match index {
+            Some(grid) => grid.get(pos).iter().for_each(|&i| test(i as usize)),
+            None => (0..self.vertex.len() / 2).for_each(test),
+        }
+        winding[0] != 0 && winding[1..].iter().all(|&w| w == 0)
+    }
+
+    /// Computes the distance from the point to the closest edge of the polygon.
+    pub fn distance(&self, pos: Point) -> f32 {
+        let mut dist = f32::INFINITY;
+        for i in 0..self.vertex.len() / 2 {
+            let (_, a, b) = self.edge(i);
+            let d = [b[0] - a[0], b[1] - a[1]];
+            let len = d[0] * d[0] + d[1] * d[1];
+            let t = if len > 0.0 {
+                (((pos[0] - a[0]) * d[0] + (pos[1] - a[1]) * d[1]) / len).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            dist = dist.min(f32::hypot(a[0] + t * d[0] - pos[0], a[1] + t * d[1] - pos[1]));
+        }
+        dist
+    }
+}
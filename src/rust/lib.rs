@@ -1,13 +1,60 @@
 
+use std::cell::RefCell;
 use std::f32::consts::PI;
+use std::ops::{Deref, Range};
+use std::rc::Rc;
 
-use js_sys::{Uint32Array, Float32Array};
+use js_sys::{Uint16Array, Uint32Array, Float32Array};
 use wasm_bindgen::prelude::*;
 
 mod earcut;
+mod adjacency;
+mod boolean;
+mod spatial;
+mod hit_test;
+mod selection;
+mod geocode;
+mod aggregate;
+mod projection;
+mod geodesic;
+mod cartogram;
+mod delaunay;
+mod chunk;
+mod outline;
+
+pub use adjacency::AdjacencyGraph;
+pub use hit_test::Intersection;
+pub use geocode::LocationIndex;
+pub use aggregate::PointAggregation;
+pub use projection::{Projection, ProjectionKind};
+pub use cartogram::Cartogram;
+pub use earcut::{Float, Triangulation, TriangulationStatus};
+pub use delaunay::{Refinement, TriangulationMode};
+pub use chunk::Chunk;
+pub use outline::LineJoin;
+
+use spatial::{RTree, Grid};
+use selection::Selection;
+use chunk::Chunks;
 
 type Point = [f32; 2];
 
+const EARTH_RADIUS: f32 = 6371.0;
+
+// Number of projections whose geometry is cached per location
+const PROJECTION_CACHE_SIZE: usize = 4;
+
+fn read_unsigned(raw: &[u8]) -> u32 {
+    (raw[0] as u32) | (raw[1] as u32) << 8
+    | (raw[2] as u32) << 16 | (raw[3] as u32) << 24
+}
+
+fn read_signed(raw: &[u8]) -> i32 {
+    (raw[0] as i32) | (raw[1] as i32) << 8
+    | (raw[2] as i32) << 16 | (raw[3] as i32) << 24
+}
+
+#[derive(Clone)]
 pub struct Polygon {
     vertex: Vec<f32>,
     holes: Vec<u32>,
@@ -16,44 +63,53 @@ pub struct Polygon {
 }
 
 impl Polygon {
-    pub fn triangulate_into(&self, triangles: &mut Vec<u32>) {
+    /// Appends the triangles of the polygon and reports how well they cover it. The indices are
+    /// relative to the first vertex of the polygon.
+    pub fn triangulate_into(&self, triangles: &mut Vec<u32>) -> Triangulation {
+        let old = triangles.len();
         earcut::triangulate_into(triangles, &self.vertex, &self.holes, self.min, self.max);
+        earcut::check(&self.vertex, &self.holes, &[], &triangles[old..])
     }
 
-    fn projected(&self) -> Polygon {
-        fn map_projection(lon: f32, lat: f32) -> Point {
-            [
-                PI + lon,
-                PI - f32::ln(f32::tan(
-                    PI / 4.0 + if f32::abs(lat) > 85.0 { f32::signum(lat) * 85.0 } else { lat } / 2.0
-                ))
-            ]
-        }
-        let mut poly = Polygon {
-            vertex: self.vertex.clone(), holes: self.holes.clone(),
-            min: [f32::MAX, f32::MAX], max: [f32::MIN, f32::MIN]
-        };
-        for i in (0..self.vertex.len()).step_by(2) {
-            let proj = map_projection(self.vertex[i], self.vertex[i + 1]);
-            poly.vertex[i] = proj[0];
-            poly.vertex[i + 1] = proj[1];
-            poly.min[0] = poly.min[0].min(proj[0]);
-            poly.min[1] = poly.min[1].min(proj[1]);
-            poly.max[0] = poly.max[0].max(proj[0]);
-            poly.max[1] = poly.max[1].max(proj[1]);
-        }
-        return poly;
+    fn rings(&self) -> impl Iterator<Item = &[f32]> {
+        (0..self.holes.len() + 1).map(move |j| {
+            let start = if j == 0 { 0 } else { self.holes[j - 1] as usize };
+            let end = if j == self.holes.len() { self.vertex.len() / 2 } else { self.holes[j] as usize };
+            &self.vertex[2 * start..2 * end]
+        })
     }
 }
 
+/// Triangulates a polygon given as flat coordinates of any precision, where `holes` contains the
+/// index of the first vertex of every hole. The indices of the triangles are appended to
+/// `triangles`.
+pub fn triangulate<T: Float>(vertex: &[T], holes: &[u32], triangles: &mut Vec<u32>) -> Triangulation {
+    let mut min = [T::from_f64(f64::MAX); 2];
+    let mut max = [T::from_f64(f64::MIN); 2];
+    for pos in vertex.chunks(2) {
+        min = [min[0].min(pos[0]), min[1].min(pos[1])];
+        max = [max[0].max(pos[0]), max[1].max(pos[1])];
+    }
+    let old = triangles.len();
+    earcut::triangulate_into(triangles, vertex, holes, min, max);
+    earcut::check(vertex, holes, &[], &triangles[old..])
+}
+
+/// A polygon of a location. The view shares the polygons it was taken from, so it stays valid
+/// after the location is freed or its projections are invalidated.
 #[wasm_bindgen]
 pub struct PolygonView {
-    poly: *const Polygon,
+    polygons: Rc<Vec<Polygon>>,
+    i: usize,
 }
 
 impl PolygonView {
-    fn new(poly: &Polygon) -> PolygonView {
-        PolygonView { poly }
+    fn new(polygons: &Rc<Vec<Polygon>>, i: usize) -> Option<PolygonView> {
+        (i < polygons.len()).then(|| PolygonView { polygons: polygons.clone(), i })
+    }
+
+    fn poly(&self) -> &Polygon {
+        &self.polygons[self.i]
     }
 }
 
@@ -61,38 +117,90 @@ impl PolygonView {
 impl PolygonView {
     #[wasm_bindgen(getter)]
     pub fn vertex(&self) -> Float32Array {
-        unsafe { Float32Array::view(&(*self.poly).vertex) }
+        unsafe { Float32Array::view(&self.poly().vertex) }
     }
     
     #[wasm_bindgen(getter)]
     pub fn holes(&self) -> Uint32Array {
-        unsafe { Uint32Array::view(&(*self.poly).holes) }
+        unsafe { Uint32Array::view(&self.poly().holes) }
     }
 
     #[wasm_bindgen(getter)]
     pub fn min(&self) -> Vec<f32> {
-        unsafe { (*self.poly).min.to_vec() }
+        self.poly().min.to_vec()
     }
 
     #[wasm_bindgen(getter)]
     pub fn max(&self) -> Vec<f32> {
-        unsafe { (*self.poly).max.to_vec() }
+        self.poly().max.to_vec()
+    }
+
+    /// Tests whether the polygon contains `pos` by checking every edge, without the spatial index
+    /// `TriangulatedData::get_intersection` uses.
+    #[wasm_bindgen]
+    pub fn contains(&self, pos: &[f32]) -> bool {
+        pos.len() >= 2 && self.poly().contains([pos[0], pos[1]], None)
     }
 }
 
-#[wasm_bindgen]
-pub struct LocationData {
+/// The polygons of a location projected with a specific projection.
+struct Projected {
+    key: Vec<u32>,
+    polygons: Rc<Vec<Polygon>>,
+    min: Point,
+    max: Point,
+}
+
+impl Projected {
+    fn new(id: &str, polygons: &[Polygon], projection: &Projection) -> Projected {
+        let mut min = [f32::MAX, f32::MAX];
+        let mut max = [f32::MIN, f32::MIN];
+        let mut projected = Vec::new();
+        for proj in polygons.iter().flat_map(|poly| projection.project_polygon(id, poly)) {
+            min[0] = min[0].min(proj.min[0]);
+            min[1] = min[1].min(proj.min[1]);
+            max[0] = max[0].max(proj.max[0]);
+            max[1] = max[1].max(proj.max[1]);
+            projected.push(proj);
+        }
+        Projected { key: projection.key(), polygons: Rc::new(projected), min, max }
+    }
+}
+
+/// The geometry of a location, shared by all handles to it.
+pub struct Location {
+    id: String,
     name: String,
-    polygons: Vec<Polygon>,
+    polygons: Rc<Vec<Polygon>>,
     min: Point,
     max: Point,
-    proj_polygons: Vec<Polygon>,
-    proj_min: Point,
-    proj_max: Point,
+    projections: RefCell<Vec<Rc<Projected>>>,
+}
+
+/// A handle to a location. Cloning it shares the location, so freeing the handle in JS does not
+/// invalidate the `TriangulatedData` and `LocationIndex` it was added to.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct LocationData {
+    loc: Rc<Location>,
+}
+
+impl Deref for LocationData {
+    type Target = Location;
+
+    fn deref(&self) -> &Location {
+        &self.loc
+    }
 }
 
 #[wasm_bindgen]
 impl LocationData {
+    /// The identifier the location was loaded with, which insets of projections refer to.
+    #[wasm_bindgen(getter)]
+    pub fn id(&self) -> String {
+        self.id.clone()
+    }
+
     #[wasm_bindgen(getter)]
     pub fn name(&self) -> String {
         self.name.clone()
@@ -113,36 +221,51 @@ impl LocationData {
         self.polygons.len()
     }
 
+    /// Returns `undefined` if the location has no polygon with the given index.
     #[wasm_bindgen]
-    pub fn get_polygon(&self, i: usize) -> PolygonView {
-        PolygonView::new(&self.polygons[i])
+    pub fn get_polygon(&self, i: usize) -> Option<PolygonView> {
+        PolygonView::new(&self.polygons, i)
     }
 
+    /// The number of projected polygons. This can differ from `count_polygons`, because polygons
+    /// are cut at the antimeridian and those outside of the projected area are removed.
     #[wasm_bindgen]
-    pub fn get_proj_polygon(&mut self, i: usize) -> PolygonView {
-        PolygonView::new(&self.proj_polygons[i])
+    pub fn count_proj_polygons(&self, projection: &Projection) -> usize {
+        self.projected(projection).polygons.len()
     }
 
-    #[wasm_bindgen(getter)]
-    pub fn proj_min(&self) -> Vec<f32> {
-        self.proj_min.to_vec()
+    /// Returns `undefined` if the location has no projected polygon with the given index.
+    #[wasm_bindgen]
+    pub fn get_proj_polygon(&self, projection: &Projection, i: usize) -> Option<PolygonView> {
+        PolygonView::new(&self.projected(projection).polygons, i)
     }
 
-    #[wasm_bindgen(getter)]
-    pub fn proj_max(&self) -> Vec<f32> {
-        self.proj_max.to_vec()
+    #[wasm_bindgen]
+    pub fn proj_min(&self, projection: &Projection) -> Vec<f32> {
+        self.projected(projection).min.to_vec()
     }
 
     #[wasm_bindgen]
-    pub fn parse_location_data(raw: &[u8]) -> LocationData {
-        fn read_unsigned(raw: &[u8]) -> u32 {
-            (raw[0] as u32) | (raw[1] as u32) << 8
-            | (raw[2] as u32) << 16 | (raw[3] as u32) << 24
-        }
-        fn read_signed(raw: &[u8]) -> i32 {
-            (raw[0] as i32) | (raw[1] as i32) << 8
-            | (raw[2] as i32) << 16 | (raw[3] as i32) << 24
-        }
+    pub fn proj_max(&self, projection: &Projection) -> Vec<f32> {
+        self.projected(projection).max.to_vec()
+    }
+
+    /// Removes the cached geometry for the given projection. Existing views of the projected
+    /// polygons stay valid.
+    #[wasm_bindgen]
+    pub fn invalidate_projection(&mut self, projection: &Projection) {
+        let key = projection.key();
+        self.projections.borrow_mut().retain(|proj| proj.key != key);
+    }
+
+    /// Removes the cached geometry for all projections.
+    #[wasm_bindgen]
+    pub fn clear_projections(&mut self) {
+        self.projections.borrow_mut().clear();
+    }
+
+    #[wasm_bindgen]
+    pub fn parse_location_data(id: String, raw: &[u8]) -> LocationData {
         let mut len = 0;
         while raw[len] != 0 {
             len += 1;
@@ -150,12 +273,7 @@ impl LocationData {
         let name = std::str::from_utf8(&raw[..len]).unwrap_or("");
         len += 1;
         let mut polygons = Vec::new();
-        let mut proj_polygons = Vec::new();
         let num_poly = read_unsigned(&raw[len..len + 4]);
-        let mut min = [f32::MAX, f32::MAX];
-        let mut max = [f32::MIN, f32::MIN];
-        let mut proj_min = [f32::MAX, f32::MAX];
-        let mut proj_max = [f32::MIN, f32::MIN];
         len += 4;
         for _ in 0..num_poly {
             let mut poly = Polygon {
@@ -183,31 +301,140 @@ impl LocationData {
                     poly.vertex.push(lat);
                 }
             }
+            polygons.push(poly);
+        }
+        LocationData::from_polygons(id, name.to_owned(), polygons)
+    }
+
+    #[wasm_bindgen]
+    pub fn serialize_location_data(&self) -> Vec<u8> {
+        fn write_signed(raw: &mut Vec<u8>, value: i32) {
+            raw.extend(value.to_le_bytes());
+        }
+        let mut raw = Vec::new();
+        raw.extend(self.name.as_bytes());
+        raw.push(0);
+        write_signed(&mut raw, self.polygons.len() as i32);
+        for poly in self.polygons.iter() {
+            write_signed(&mut raw, poly.holes.len() as i32 + 1);
+            for ring in poly.rings() {
+                write_signed(&mut raw, (ring.len() / 2) as i32);
+                for &cord in ring {
+                    write_signed(&mut raw, (cord as f64 * 180.0e7 / std::f64::consts::PI).round() as i32);
+                }
+            }
+        }
+        raw
+    }
+
+    /// Merges this location with `other`, keeping the id and name of this location. See
+    /// `union_all` for the meaning of `tolerance`.
+    #[wasm_bindgen]
+    pub fn union(&self, other: &LocationData, tolerance: f32) -> LocationData {
+        LocationData::union_all(self.id.clone(), self.name.clone(), &[self, other], tolerance)
+    }
+
+    #[wasm_bindgen]
+    pub fn intersection(&self, other: &LocationData, tolerance: f32) -> LocationData {
+        LocationData::combine(self.id.clone(), self.name.clone(), &[self, other], tolerance, boolean::Operation::Intersection)
+    }
+
+    #[wasm_bindgen]
+    pub fn difference(&self, other: &LocationData, tolerance: f32) -> LocationData {
+        LocationData::combine(self.id.clone(), self.name.clone(), &[self, other], tolerance, boolean::Operation::Difference)
+    }
+}
+
+impl LocationData {
+    fn from_polygons(id: String, name: String, polygons: Vec<Polygon>) -> LocationData {
+        let mut min = [f32::MAX, f32::MAX];
+        let mut max = [f32::MIN, f32::MIN];
+        for poly in &polygons {
             min[0] = min[0].min(poly.min[0]);
             min[1] = min[1].min(poly.min[1]);
             max[0] = max[0].max(poly.max[0]);
             max[1] = max[1].max(poly.max[1]);
-            let proj = poly.projected();
-            proj_min[0] = proj_min[0].min(proj.min[0]);
-            proj_min[1] = proj_min[1].min(proj.min[1]);
-            proj_max[0] = proj_max[0].max(proj.max[0]);
-            proj_max[1] = proj_max[1].max(proj.max[1]);
-            polygons.push(poly);
-            proj_polygons.push(proj);
-        }
-        return LocationData {
-            name: name.to_owned(),
-            polygons: polygons,
-            proj_polygons: proj_polygons,
-            min: min, max: max,
-            proj_min: proj_min, proj_max: proj_max,
+        }
+        let polygons = Rc::new(polygons);
+        LocationData { loc: Rc::new(Location { id, name, polygons, min, max, projections: RefCell::new(Vec::new()) }) }
+    }
+
+    /// Merges the given locations into a single new location without interior borders. Vertices
+    /// closer than `tolerance` kilometres are snapped together and slivers thinner than it are
+    /// removed, so that small gaps between neighbouring locations do not remain.
+    pub fn union_all(id: String, name: String, locs: &[&LocationData], tolerance: f32) -> LocationData {
+        LocationData::combine(id, name, locs, tolerance, boolean::Operation::Union)
+    }
+
+    fn combine(id: String, name: String, locs: &[&LocationData], tolerance: f32, op: boolean::Operation) -> LocationData {
+        let polygons = boolean::combine(
+            &locs.iter().map(|loc| &loc.polygons[..]).collect::<Vec<_>>(),
+            tolerance / EARTH_RADIUS, op
+        );
+        LocationData::from_polygons(id, name, polygons)
+    }
+}
+
+impl Location {
+    /// Returns the polygons projected with the given projection. They are computed on first use
+    /// and cached until invalidated, only the most recently used projections are kept.
+    fn projected(&self, projection: &Projection) -> Rc<Projected> {
+        let key = projection.key();
+        let mut projections = self.projections.borrow_mut();
+        let projected = match projections.iter().position(|proj| proj.key == key) {
+            Some(i) => projections.remove(i),
+            None => Rc::new(Projected::new(&self.id, &self.polygons, projection)),
         };
+        projections.insert(0, projected.clone());
+        projections.truncate(PROJECTION_CACHE_SIZE);
+        projected
     }
 }
 
+/// The buffers of `TriangulatedData` that are uploaded to the GPU.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RenderBuffer {
+    Vertex,
+    Color,
+    Triangles,
+    /// Both `outline_triangles` and `outline_normals`
+    Outlines,
+}
+
+/// The parts of the buffers of `TriangulatedData` that belong to one location. The vertex range
+/// counts coordinates, so the colors of the location are at half of it.
+#[derive(Clone, Default)]
+struct LocationRanges {
+    vertex: Range<usize>,
+    triangles: Range<usize>,
+    polygons: Range<usize>,
+    outlines: Range<usize>,
+    triangulated: bool,
+    outlined: bool,
+}
+
+/// A triangulated location before it is copied into the buffers. Triangles index the vertices of
+/// the location and polygons start at its first triangle.
+struct LocationMesh {
+    vertex: Vec<f32>,
+    triangles: Vec<u32>,
+    polygons: Vec<usize>,
+    bounds: Vec<(Point, Point)>,
+    edge_index: Vec<Option<Grid>>,
+    geometry: Rc<Vec<Polygon>>,
+    triangulation: Triangulation,
+}
+
+fn shift(range: &mut Range<usize>, delta: isize) {
+    range.start = range.start.wrapping_add_signed(delta);
+    range.end = range.end.wrapping_add_signed(delta);
+}
+
 #[wasm_bindgen]
 pub struct TriangulatedData {
-    locs: Vec<*const LocationData>,
+    locs: Vec<LocationData>,
+    ranges: Vec<LocationRanges>,
     vertex: Vec<f32>,
     color: Vec<f32>,
     triangles: Vec<u32>,
@@ -216,6 +443,23 @@ pub struct TriangulatedData {
     outline_normals: Vec<f32>,
     min: Point,
     max: Point,
+    poly_locs: Vec<[usize; 2]>,
+    poly_bounds: Vec<(Point, Point)>,
+    poly_index: RTree,
+    edge_index: Vec<Option<Grid>>,
+    proj: bool,
+    projection: Projection,
+    max_edge_angle: f32,
+    geometry: Vec<Rc<Vec<Polygon>>>,
+    triangulations: Vec<Triangulation>,
+    mode: TriangulationMode,
+    refinement: Refinement,
+    outline_proj: bool,
+    colored: usize,
+    dirty: [Option<Range<usize>>; 4],
+    chunks: Chunks,
+    line_join: LineJoin,
+    miter_limit: f32,
 }
 
 #[wasm_bindgen]
@@ -245,6 +489,24 @@ impl TriangulatedData {
         unsafe { Float32Array::view(&self.outline_normals) }
     }
 
+    /// The vertices of all chunks, see `generate_chunks`.
+    #[wasm_bindgen(getter)]
+    pub fn chunk_vertex(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.chunks.vertex) }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn chunk_color(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.chunks.color) }
+    }
+
+    /// The triangles of all chunks, indexing the vertices relative to the first vertex of their
+    /// chunk.
+    #[wasm_bindgen(getter)]
+    pub fn chunk_triangles(&self) -> Uint16Array {
+        unsafe { Uint16Array::view(&self.chunks.triangles) }
+    }
+
     #[wasm_bindgen(getter)]
     pub fn min(&self) -> Vec<f32> {
         self.min.to_vec()
@@ -258,136 +520,666 @@ impl TriangulatedData {
     #[wasm_bindgen]
     pub fn new() -> TriangulatedData {
         TriangulatedData {
-            locs: Vec::new(), vertex: Vec::new(), color: Vec::new(),
+            locs: Vec::new(), ranges: Vec::new(), vertex: Vec::new(), color: Vec::new(),
             triangles: Vec::new(), polygons: Vec::new(),
             outline_triangles: Vec::new(), outline_normals: Vec::new(),
             min: [f32::MAX, f32::MAX], max: [f32::MIN, f32::MIN],
+            poly_locs: Vec::new(), poly_bounds: Vec::new(),
+            poly_index: RTree::new(&[]), edge_index: Vec::new(), proj: false,
+            projection: Projection::default(), max_edge_angle: 0.0, geometry: Vec::new(),
+            triangulations: Vec::new(), mode: TriangulationMode::Earcut, refinement: Refinement::default(),
+            outline_proj: false, colored: 0, dirty: [None, None, None, None], chunks: Chunks::default(),
+            line_join: LineJoin::Miter, miter_limit: 4.0,
         }
     }
+
+    /// Sets the projection used by `triangulate`. This has no effect on already triangulated
+    /// locations.
+    #[wasm_bindgen]
+    pub fn set_projection(&mut self, projection: &Projection) {
+        self.projection = projection.clone();
+    }
+
+    /// Makes `triangulate` insert points along the great circle of edges that are longer than
+    /// `max_angle` degrees, so that they are curved correctly when projected. Zero disables this.
+    #[wasm_bindgen]
+    pub fn set_max_edge_angle(&mut self, max_angle: f32) {
+        self.max_edge_angle = max_angle * PI / 180.0;
+    }
     
+    /// Selects the triangulation used by `triangulate`. Delaunay triangulations are refined by
+    /// inserting points until no edge is longer than `max_edge_length` and no angle is smaller than
+    /// `min_angle` degrees, where zero disables the limit. The lengths are in the units of the
+    /// triangulated geometry.
+    #[wasm_bindgen]
+    pub fn set_triangulation_mode(&mut self, mode: TriangulationMode, max_edge_length: f32, min_angle: f32) {
+        self.mode = mode;
+        self.refinement = Refinement { max_edge_length, min_angle: min_angle * PI / 180.0 };
+    }
+
+    /// Selects how `generate_outlines` connects the segments of outlines. Miter joins longer than
+    /// `miter_limit` times the line width are beveled. All outlines are generated again by the
+    /// next call to `generate_outlines`.
+    #[wasm_bindgen]
+    pub fn set_line_join(&mut self, join: LineJoin, miter_limit: f32) {
+        self.line_join = join;
+        self.miter_limit = miter_limit;
+        for range in &mut self.ranges {
+            range.outlined = false;
+        }
+    }
+
+    /// Adds a location, which is triangulated by the next call to `triangulate`.
     #[wasm_bindgen]
     pub fn add_location(&mut self, loc: &LocationData) {
-        self.locs.push(loc);
+        let range = |len| len..len;
+        self.locs.push(loc.clone());
+        self.ranges.push(LocationRanges {
+            vertex: range(self.vertex.len()),
+            triangles: range(self.triangles.len()),
+            polygons: range(self.polygons.len()),
+            outlines: range(self.outline_triangles.len()),
+            triangulated: false,
+            outlined: false,
+        });
+        self.geometry.push(Rc::default());
+        self.triangulations.push(Triangulation { status: TriangulationStatus::Ok, deviation: 0.0 });
+    }
+
+    /// Removes the location and its part of the buffers. The indices of all following locations
+    /// move down by one.
+    #[wasm_bindgen]
+    pub fn remove_location(&mut self, location: usize) {
+        self.set_outlines(location, Vec::new(), Vec::new());
+        self.set_vertices(location, Vec::new(), Vec::new());
+        self.set_polygons(location, Vec::new(), Vec::new(), Vec::new());
+        self.locs.remove(location);
+        self.ranges.remove(location);
+        self.geometry.remove(location);
+        self.triangulations.remove(location);
+        for pl in &mut self.poly_locs {
+            if pl[0] > location {
+                pl[0] -= 1;
+            }
+        }
+        self.update();
+    }
+
+    /// Replaces the location, which is triangulated again by the next call to `triangulate`. Until
+    /// then its old triangles stay in the buffers, but it is not found by hit tests.
+    #[wasm_bindgen]
+    pub fn replace_location(&mut self, location: usize, loc: &LocationData) {
+        self.locs[location] = loc.clone();
+        self.set_polygons(location, Vec::new(), Vec::new(), Vec::new());
+        self.geometry[location] = Rc::default();
+        self.ranges[location].triangulated = false;
+        self.ranges[location].outlined = false;
+        self.update();
+    }
+
+    /// Removes all locations and empties the buffers.
+    #[wasm_bindgen]
+    pub fn clear(&mut self) {
+        self.locs.clear();
+        self.ranges.clear();
+        self.vertex.clear();
+        self.color.clear();
+        self.triangles.clear();
+        self.polygons.clear();
+        self.outline_triangles.clear();
+        self.outline_normals.clear();
+        self.poly_locs.clear();
+        self.poly_bounds.clear();
+        self.edge_index.clear();
+        self.geometry.clear();
+        self.triangulations.clear();
+        for buffer in [RenderBuffer::Vertex, RenderBuffer::Color, RenderBuffer::Triangles, RenderBuffer::Outlines] {
+            self.mark_dirty(buffer, 0..0);
+        }
+        self.update();
     }
 
+    /// Triangulates all locations that were added or replaced since the last call. Changing `proj`
+    /// triangulates all of them again.
     #[wasm_bindgen]
     pub fn triangulate(&mut self, proj: bool) {
-        for (i, &loc) in self.locs.iter().enumerate() {
-            let polys = unsafe {
-                if proj { &(*loc).proj_polygons } else { &(*loc).polygons }
-            };
-            for poly in polys {
-                let old = self.triangles.len();
-                poly.triangulate_into(&mut self.triangles);
-                for j in old..self.triangles.len() {
-                    self.triangles[j] += (self.vertex.len() / 2) as u32;
-                }
-                self.vertex.extend(&poly.vertex);
-                self.polygons.push(old);
-                for _ in 0..poly.vertex.len() / 2 {
-                    self.color.push((i as f32 + 0.5) / self.locs.len() as f32);
-                }
-                self.min[0] = self.min[0].min(poly.min[0]);
-                self.min[1] = self.min[1].min(poly.min[1]);
-                self.max[0] = self.max[0].max(poly.max[0]);
-                self.max[1] = self.max[1].max(poly.max[1]);
+        if proj != self.proj {
+            self.proj = proj;
+            for range in &mut self.ranges {
+                range.triangulated = false;
             }
         }
+        for l in 0..self.locs.len() {
+            if self.ranges[l].triangulated {
+                continue;
+            }
+            let mesh = self.triangulate_location(l);
+            self.set_vertices(l, mesh.vertex, mesh.triangles);
+            self.set_polygons(l, mesh.polygons, mesh.bounds, mesh.edge_index);
+            self.geometry[l] = mesh.geometry;
+            self.triangulations[l] = mesh.triangulation;
+            self.ranges[l].triangulated = true;
+            self.ranges[l].outlined = false;
+        }
+        self.update();
+    }
+
+    /// The part of the buffer that changed since the last call to `clear_dirty` as start and end
+    /// index, or `undefined` if nothing changed. If the length of the buffer changed, it has to be
+    /// uploaded again completely.
+    #[wasm_bindgen]
+    pub fn dirty_range(&self, buffer: RenderBuffer) -> Option<Vec<usize>> {
+        let len = match buffer {
+            RenderBuffer::Vertex => self.vertex.len(),
+            RenderBuffer::Color => self.color.len(),
+            RenderBuffer::Triangles => self.triangles.len(),
+            RenderBuffer::Outlines => self.outline_triangles.len(),
+        };
+        self.dirty[buffer as usize].as_ref().map(|range| vec![range.start.min(len), range.end.min(len)])
+    }
+
+    /// Marks all buffers as uploaded.
+    #[wasm_bindgen]
+    pub fn clear_dirty(&mut self) {
+        self.dirty = [None, None, None, None];
+    }
+
+    /// Whether the polygons of the location were triangulated correctly by `triangulate`.
+    #[wasm_bindgen]
+    pub fn triangulation_status(&self, location: usize) -> TriangulationStatus {
+        self.triangulations[location].status
+    }
+
+    /// The largest relative difference between the area of a polygon of the location and the area
+    /// of its triangles.
+    #[wasm_bindgen]
+    pub fn triangulation_deviation(&self, location: usize) -> f32 {
+        self.triangulations[location].deviation
     }
 
+    /// The indices of all locations that were not triangulated correctly.
+    #[wasm_bindgen]
+    pub fn failed_triangulations(&self) -> Vec<usize> {
+        self.triangulations.iter().enumerate()
+            .filter(|(_, triangulation)| triangulation.status != TriangulationStatus::Ok)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Generates the outlines of all locations that were triangulated since the last call. Changing
+    /// `proj` generates all of them again.
+    #[wasm_bindgen]
     pub fn generate_outlines(&mut self, proj: bool) {
-        for &loc in &self.locs {
-            let polys = unsafe {
-                if proj { &(*loc).proj_polygons } else { &(*loc).polygons }
-            };
-            for poly in polys {
-                for j in 0..poly.holes.len() + 1 {
-                    let start = if j == 0 { 0 } else { poly.holes[j - 1] as usize };
-                    let end = if j == poly.holes.len() { poly.vertex.len() / 2 } else { poly.holes[j] as usize };
-                    let part_len = end - start;
-                    for i in 0..part_len {
-                        let curr = (poly.vertex[2 * (start + i)], poly.vertex[2 * (start + i) + 1]);
-                        let last = (
-                            poly.vertex[2 * (start + (part_len + i - 1) % part_len)],
-                            poly.vertex[2 * (start + (part_len + i - 1) % part_len) + 1],
-                        );
-                        let next = (
-                            poly.vertex[2 * (start + (i + 1) % part_len)],
-                            poly.vertex[2 * (start + (i + 1) % part_len) + 1],
-                        );
-                        let from_last = (curr.0 - last.0, curr.1 - last.1);
-                        let to_next = (next.0 - curr.0, next.1 - curr.1);
-                        self.outline_triangles.extend([
-                            curr.0, curr.1,   curr.0, curr.1,   next.0, next.1, // Line
-                            curr.0, curr.1,   next.0, next.1,   next.0, next.1,
-
-                            curr.0, curr.1,   curr.0, curr.1,   curr.0, curr.1, // Corner
-                            curr.0, curr.1,   curr.0, curr.1,   curr.0, curr.1,
-                        ]);
-                        self.outline_normals.extend([
-                            to_next.1, -to_next.0,   -to_next.1, to_next.0,   to_next.1, -to_next.0,
-                            -to_next.1, to_next.0,   -to_next.1, to_next.0,   to_next.1, -to_next.0,
-
-                            -from_last.1, from_last.0, from_last.1, -from_last.0, -to_next.1, to_next.0,
-                            -from_last.1, from_last.0, from_last.1, -from_last.0, to_next.1, -to_next.0,
-                        ])
-                    }
-                }
+        if proj != self.outline_proj {
+            self.outline_proj = proj;
+            for range in &mut self.ranges {
+                range.outlined = false;
+            }
+        }
+        for l in 0..self.locs.len() {
+            if self.ranges[l].outlined {
+                continue;
+            }
+            let mut outline_triangles = Vec::new();
+            let mut outline_normals = Vec::new();
+            let polys = self.location_polygons(l, proj);
+            for poly in polys.iter() {
+                outline::outline_into(poly, self.line_join, self.miter_limit, &mut outline_triangles, &mut outline_normals);
             }
+            self.set_outlines(l, outline_triangles, outline_normals);
+            self.ranges[l].outlined = true;
         }
     }
 
+    /// The indices of the triangles of the location in `triangles` as offset and count, so that
+    /// the location can be drawn on its own. The range is empty for locations that do not exist.
     #[wasm_bindgen]
-    pub fn get_intersection(&self, pos: Vec<f32>, proj: bool) -> Option<Vec<usize>> {
-        let mut poly_i = 0;
-        for (l, &loc) in self.locs.iter().enumerate() {
-            let (min, max, polys) = unsafe {
-                if proj {
-                    (&(*loc).proj_min, &(*loc).proj_max, &(*loc).proj_polygons)
-                } else {
-                    (&(*loc).min, &(*loc).max, &(*loc).polygons)
-                }
-            };
-            if pos[0] >= min[0] && pos[1] >= min[1] && pos[0] <= max[0] && pos[1] <= max[1] {
-                for (p, poly) in polys.iter().enumerate() {
-                    let min = poly.min;
-                    let max = poly.max;
-                    if pos[0] >= min[0] && pos[1] >= min[1] && pos[0] <= max[0] && pos[1] <= max[1] {
-                        let start = self.polygons[poly_i];
-                        let end = if poly_i + 1 < self.polygons.len() { self.polygons[poly_i + 1] } else { self.triangles.len() };
-                        for j in (start..end).step_by(3) {
-                            let v1 = [
-                                self.vertex[2 * self.triangles[j] as usize],
-                                self.vertex[2 * self.triangles[j] as usize + 1]
-                            ];
-                            let v2 = [
-                                self.vertex[2 * self.triangles[j + 1] as usize],
-                                self.vertex[2 * self.triangles[j + 1] as usize + 1]
-                            ];
-                            let v3 = [
-                                self.vertex[2 * self.triangles[j + 2] as usize],
-                                self.vertex[2 * self.triangles[j + 2] as usize + 1]
-                            ];
-                            fn sign(p1: &Vec<f32>, p2: &Point, p3: &Point) -> f32 {
-                                return (p1[0] - p3[0]) * (p2[1] - p3[1]) - (p2[0] - p3[0]) * (p1[1] - p3[1]);
-                            }
-                            let d1 = sign(&pos, &v1, &v2);
-                            let d2 = sign(&pos, &v2, &v3);
-                            let d3 = sign(&pos, &v3, &v1);
-                            let has_neg = (d1 < 0.0) || (d2 < 0.0) || (d3 < 0.0);
-                            let has_pos = (d1 > 0.0) || (d2 > 0.0) || (d3 > 0.0);
-                            if !(has_neg && has_pos) {
-                                return Some(vec![l, p]);
-                            }
-                        }
-                    }
-                    poly_i += 1;
+    pub fn fill_range(&self, location: usize) -> Vec<usize> {
+        self.ranges.get(location)
+            .map_or(vec![0, 0], |range| vec![range.triangles.start, range.triangles.len()])
+    }
+
+    /// The vertices of the outlines of the location in `outline_triangles` and `outline_normals`
+    /// as offset and count. The range is empty for locations that do not exist.
+    #[wasm_bindgen]
+    pub fn outline_range(&self, location: usize) -> Vec<usize> {
+        self.ranges.get(location)
+            .map_or(vec![0, 0], |range| vec![range.outlines.start / 2, range.outlines.len() / 2])
+    }
+
+    /// Like `fill_range`, but for the 16 bit indices in `chunk_triangles`. Because a location can
+    /// span several chunks, its triangles are given as triples of chunk index, offset and count.
+    /// The list is empty if the chunks were not generated or the location does not exist.
+    #[wasm_bindgen]
+    pub fn chunk_fill_ranges(&self, location: usize) -> Vec<usize> {
+        self.chunks.locations.get(location).cloned().unwrap_or_default()
+    }
+
+    /// Splits the triangles and outlines into chunks that can be drawn with 16 bit indices, for
+    /// devices without support for 32 bit indices. The chunks have to be generated again after
+    /// the locations change.
+    #[wasm_bindgen]
+    pub fn generate_chunks(&mut self) {
+        let locations: Vec<_> = self.ranges.iter()
+            .map(|range| (range.triangles.clone(), range.outlines.start / 2..range.outlines.end / 2))
+            .collect();
+        self.chunks = Chunks::split(&self.vertex, &self.color, &self.triangles, &locations);
+    }
+
+    #[wasm_bindgen]
+    pub fn count_chunks(&self) -> usize {
+        self.chunks.chunks.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn get_chunk(&self, i: usize) -> Chunk {
+        self.chunks.chunks[i].clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn compute_adjacency(&self, tolerance: f32) -> AdjacencyGraph {
+        let locs: Vec<&LocationData> = self.locs.iter().collect();
+        AdjacencyGraph::compute(&locs, tolerance)
+    }
+
+    /// Merges the locations at the given indices into a new location, see `LocationData::union_all`.
+    /// Indices of locations that do not exist are ignored.
+    #[wasm_bindgen]
+    pub fn dissolve(&self, id: String, name: String, indices: Vec<usize>, tolerance: f32) -> LocationData {
+        let locs: Vec<&LocationData> = indices.iter().filter_map(|&i| self.locs.get(i)).collect();
+        LocationData::union_all(id, name, &locs, tolerance)
+    }
+
+    /// Aggregates the weighted points, given in degrees, per location.
+    #[wasm_bindgen]
+    pub fn aggregate_points(&self, cords: Vec<f32>, weights: Vec<f32>) -> PointAggregation {
+        let locs: Vec<&LocationData> = self.locs.iter().collect();
+        PointAggregation::compute(&locs, &cords, &weights)
+    }
+
+    /// Computes a cartogram of the locations with one value per location, using the projection
+    /// set with `set_projection`.
+    #[wasm_bindgen]
+    pub fn cartogram(&self, values: Vec<f32>, iterations: usize) -> Cartogram {
+        let locs: Vec<&LocationData> = self.locs.iter().collect();
+        Cartogram::compute(&locs, &values, &self.projection, iterations)
+    }
+
+    /// Scales every location around its label point by its value, see `cartogram`.
+    #[wasm_bindgen]
+    pub fn scaled_cartogram(&self, values: Vec<f32>) -> Cartogram {
+        let locs: Vec<&LocationData> = self.locs.iter().collect();
+        Cartogram::compute_scaled(&locs, &values, &self.projection)
+    }
+
+    /// Replaces every location with a circle sized by its value, see `cartogram`.
+    #[wasm_bindgen]
+    pub fn dorling_cartogram(&self, values: Vec<f32>, iterations: usize) -> Cartogram {
+        let locs: Vec<&LocationData> = self.locs.iter().collect();
+        Cartogram::compute_dorling(&locs, &values, &self.projection, iterations)
+    }
+
+    /// Returns all locations touching the box, or if `contains` is set, the locations that are
+    /// completely inside of it. Nothing is selected if a corner has less than two coordinates.
+    #[wasm_bindgen]
+    pub fn select_box(&self, min: Vec<f32>, max: Vec<f32>, proj: bool, contains: bool) -> Vec<usize> {
+        if min.len() < 2 || max.len() < 2 {
+            return Vec::new();
+        }
+        self.select(&Selection::from_box([min[0], min[1]], [max[0], max[1]]), proj, contains)
+    }
+
+    /// Same as `select_box`, but for a freehand lasso given as a flat list of coordinates. Nothing is
+    /// selected if the lasso has less than three points or an odd number of coordinates.
+    #[wasm_bindgen]
+    pub fn select_lasso(&self, lasso: Vec<f32>, proj: bool, contains: bool) -> Vec<usize> {
+        if lasso.len() < 6 || !lasso.len().is_multiple_of(2) {
+            return Vec::new();
+        }
+        let ring = lasso.chunks_exact(2).map(|p| [p[0], p[1]]).collect();
+        self.select(&Selection::new(ring), proj, contains)
+    }
+
+    /// Returns the polygon `polygon` of location `location` as it was triangulated, or `undefined`
+    /// if there is no such polygon.
+    #[wasm_bindgen]
+    pub fn get_polygon(&self, location: usize, polygon: usize) -> Option<PolygonView> {
+        self.poly_locs.binary_search(&[location, polygon]).ok()?;
+        PolygonView::new(self.geometry.get(location)?, polygon)
+    }
+
+    /// Converts a position on the map to longitude and latitude in degrees. Returns `undefined`
+    /// if the position is outside of the map.
+    #[wasm_bindgen]
+    pub fn get_coordinates(&self, pos: Vec<f32>) -> Option<Vec<f32>> {
+        if self.proj {
+            self.projection.invert_point(pos[0], pos[1])
+        } else if pos[0].abs() <= PI && pos[1].abs() <= PI / 2.0 {
+            Some(vec![pos[0] * 180.0 / PI, pos[1] * 180.0 / PI])
+        } else {
+            None
+        }
+    }
+
+    /// Finds the polygon containing `pos`. If there is none, the closest location within a
+    /// distance of `tolerance` is returned instead.
+    #[wasm_bindgen]
+    pub fn get_intersection(&self, pos: Vec<f32>, tolerance: f32) -> Option<Intersection> {
+        if pos.len() < 2 {
+            return None;
+        }
+        let pos = [pos[0], pos[1]];
+        let mut candidates = Vec::new();
+        self.poly_index.search(
+            [pos[0] - tolerance, pos[1] - tolerance], [pos[0] + tolerance, pos[1] + tolerance],
+            |i| candidates.push(i)
+        );
+        candidates.sort();
+        for &i in &candidates {
+            if self.polygon(i).contains(pos, self.edge_index[i].as_ref()) {
+                return Some(Intersection::new(self.poly_locs[i][0], self.poly_locs[i][1], 0.0));
+            }
+        }
+        if tolerance > 0.0 {
+            let mut closest: Option<Intersection> = None;
+            for &i in &candidates {
+                let dist = self.polygon(i).distance(pos);
+                if dist <= tolerance && closest.as_ref().map(|c| dist < c.distance()).unwrap_or(true) {
+                    closest = Some(Intersection::new(self.poly_locs[i][0], self.poly_locs[i][1], dist));
                 }
-            } else {
-                poly_i += polys.len();
             }
+            closest
+        } else {
+            None
         }
-        return None;
     }
 }
 
+impl TriangulatedData {
+    fn select(&self, selection: &Selection, proj: bool, contains: bool) -> Vec<usize> {
+        (0..self.locs.len())
+            .filter(|&l| selection.matches(&self.location_polygons(l, proj), contains))
+            .collect()
+    }
+
+    fn location_polygons(&self, l: usize, proj: bool) -> Rc<Vec<Polygon>> {
+        let loc = &self.locs[l];
+        if proj == self.proj && self.ranges[l].triangulated {
+            self.geometry[l].clone()
+        } else if proj {
+            loc.projected(&self.projection).polygons.clone()
+        } else {
+            loc.polygons.clone()
+        }
+    }
+
+    fn polygon(&self, i: usize) -> &Polygon {
+        let [l, p] = self.poly_locs[i];
+        &self.geometry[l][p]
+    }
+
+    fn triangulate_location(&self, l: usize) -> LocationMesh {
+        let loc = &self.locs[l];
+        let mut mesh = LocationMesh {
+            vertex: Vec::new(), triangles: Vec::new(), polygons: Vec::new(), bounds: Vec::new(),
+            edge_index: Vec::new(), geometry: Rc::default(),
+            // The worst triangulation of all polygons is reported for the location
+            triangulation: Triangulation { status: TriangulationStatus::Ok, deviation: 0.0 },
+        };
+        // Densified geometry is not cached in the locations
+        mesh.geometry = if self.max_edge_angle > 0.0 {
+            Rc::new(loc.polygons.iter().flat_map(|poly| {
+                let poly = poly.densified(self.max_edge_angle);
+                if self.proj { self.projection.project_polygon(&loc.id, &poly) } else { vec![poly] }
+            }).collect())
+        } else if self.proj {
+            loc.projected(&self.projection).polygons.clone()
+        } else {
+            loc.polygons.clone()
+        };
+        for poly in mesh.geometry.iter() {
+            let old = mesh.triangles.len();
+            let mut result = poly.triangulate_into(&mut mesh.triangles);
+            let mut steiner = Vec::new();
+            if self.mode == TriangulationMode::Delaunay {
+                poly.delaunay_into(&mut mesh.triangles, old, &mut steiner, &self.refinement);
+                // The ear clipping checked above is replaced, so the final mesh is checked instead
+                result = earcut::check(&poly.vertex, &poly.holes, &steiner, &mesh.triangles[old..]);
+            }
+            mesh.triangulation.status = mesh.triangulation.status.max(result.status);
+            mesh.triangulation.deviation = mesh.triangulation.deviation.max(result.deviation);
+            for j in old..mesh.triangles.len() {
+                mesh.triangles[j] += (mesh.vertex.len() / 2) as u32;
+            }
+            mesh.vertex.extend(&poly.vertex);
+            mesh.vertex.extend(&steiner);
+            mesh.polygons.push(old);
+            mesh.bounds.push((poly.min, poly.max));
+            mesh.edge_index.push(poly.build_edge_index());
+        }
+        mesh
+    }
+
+    /// Replaces the vertices and triangles of the location, moving the following locations.
+    fn set_vertices(&mut self, l: usize, vertex: Vec<f32>, triangles: Vec<u32>) {
+        let old = self.ranges[l].clone();
+        let color = (l as f32 + 0.5) / self.locs.len() as f32;
+        let offset = (old.vertex.start / 2) as u32;
+        let vertex_delta = vertex.len() as isize - old.vertex.len() as isize;
+        let triangle_delta = triangles.len() as isize - old.triangles.len() as isize;
+        self.color.splice(old.vertex.start / 2..old.vertex.end / 2, vec![color; vertex.len() / 2]);
+        self.vertex.splice(old.vertex.clone(), vertex);
+        self.triangles.splice(old.triangles.clone(), triangles.into_iter().map(|t| t + offset));
+        self.ranges[l].vertex.end = old.vertex.end.wrapping_add_signed(vertex_delta);
+        self.ranges[l].triangles.end = old.triangles.end.wrapping_add_signed(triangle_delta);
+        // The triangles of the following locations index moved vertices
+        let moved = self.ranges[l].triangles.end;
+        if vertex_delta != 0 {
+            for t in &mut self.triangles[moved..] {
+                *t = t.wrapping_add_signed(vertex_delta as i32 / 2);
+            }
+        }
+        for p in &mut self.polygons[old.polygons.end..] {
+            *p = p.wrapping_add_signed(triangle_delta);
+        }
+        for range in &mut self.ranges[l + 1..] {
+            shift(&mut range.vertex, vertex_delta);
+            shift(&mut range.triangles, triangle_delta);
+        }
+        let vertex_end = if vertex_delta == 0 { old.vertex.end } else { self.vertex.len() };
+        let triangle_end = if vertex_delta == 0 && triangle_delta == 0 { old.triangles.end } else { self.triangles.len() };
+        self.mark_dirty(RenderBuffer::Vertex, old.vertex.start..vertex_end);
+        self.mark_dirty(RenderBuffer::Color, old.vertex.start / 2..vertex_end / 2);
+        self.mark_dirty(RenderBuffer::Triangles, old.triangles.start..triangle_end);
+    }
+
+    /// Replaces the polygons of the location used for hit tests. They start at the triangles of
+    /// the location, so `set_vertices` has to be called first.
+    fn set_polygons(&mut self, l: usize, polygons: Vec<usize>, bounds: Vec<(Point, Point)>, edge_index: Vec<Option<Grid>>) {
+        let old = self.ranges[l].polygons.clone();
+        let start = self.ranges[l].triangles.start;
+        let delta = polygons.len() as isize - old.len() as isize;
+        self.poly_locs.splice(old.clone(), (0..polygons.len()).map(|p| [l, p]));
+        self.polygons.splice(old.clone(), polygons.into_iter().map(|p| p + start));
+        self.poly_bounds.splice(old.clone(), bounds);
+        self.edge_index.splice(old.clone(), edge_index);
+        self.ranges[l].polygons.end = old.end.wrapping_add_signed(delta);
+        for range in &mut self.ranges[l + 1..] {
+            shift(&mut range.polygons, delta);
+        }
+    }
+
+    /// Replaces the outlines of the location, moving the following locations.
+    fn set_outlines(&mut self, l: usize, triangles: Vec<f32>, normals: Vec<f32>) {
+        let old = self.ranges[l].outlines.clone();
+        let delta = triangles.len() as isize - old.len() as isize;
+        self.outline_triangles.splice(old.clone(), triangles);
+        self.outline_normals.splice(old.clone(), normals);
+        self.ranges[l].outlines.end = old.end.wrapping_add_signed(delta);
+        for range in &mut self.ranges[l + 1..] {
+            shift(&mut range.outlines, delta);
+        }
+        let end = if delta == 0 { old.end } else { self.outline_triangles.len() };
+        self.mark_dirty(RenderBuffer::Outlines, old.start..end);
+    }
+
+    fn mark_dirty(&mut self, buffer: RenderBuffer, range: Range<usize>) {
+        let dirty = &mut self.dirty[buffer as usize];
+        *dirty = Some(match dirty.take() {
+            Some(old) => old.start.min(range.start)..old.end.max(range.end),
+            None => range,
+        });
+    }
+
+    /// Updates everything that depends on all locations after some of them changed.
+    fn update(&mut self) {
+        // The colors are the location indices relative to the number of locations
+        if self.colored != self.locs.len() {
+            self.colored = self.locs.len();
+            for (l, range) in self.ranges.iter().enumerate() {
+                let color = (l as f32 + 0.5) / self.locs.len() as f32;
+                self.color[range.vertex.start / 2..range.vertex.end / 2].fill(color);
+            }
+            let len = self.color.len();
+            self.mark_dirty(RenderBuffer::Color, 0..len);
+        }
+        self.min = [f32::MAX, f32::MAX];
+        self.max = [f32::MIN, f32::MIN];
+        for (min, max) in &self.poly_bounds {
+            self.min[0] = self.min[0].min(min[0]);
+            self.min[1] = self.min[1].min(min[1]);
+            self.max[0] = self.max[0].max(max[0]);
+            self.max[1] = self.max[1].max(max[1]);
+        }
+        self.poly_index = RTree::new(&self.poly_bounds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A location with a single regular polygon of `n` vertices around the given center
+    fn location(center: Point, n: usize) -> LocationData {
+        let mut poly = Polygon {
+            vertex: Vec::new(), holes: Vec::new(),
+            min: [f32::MAX, f32::MAX], max: [f32::MIN, f32::MIN]
+        };
+        for i in 0..n {
+            let angle = 2.0 * PI * i as f32 / n as f32;
+            let pos = [center[0] + 0.1 * angle.cos(), center[1] + 0.1 * angle.sin()];
+            poly.min = [poly.min[0].min(pos[0]), poly.min[1].min(pos[1])];
+            poly.max = [poly.max[0].max(pos[0]), poly.max[1].max(pos[1])];
+            poly.vertex.extend(pos);
+        }
+        LocationData::from_polygons(format!("{}", n), format!("{}", n), vec![poly])
+    }
+
+    fn build(locs: &[&LocationData]) -> TriangulatedData {
+        let mut data = TriangulatedData::new();
+        for loc in locs {
+            data.add_location(loc);
+        }
+        data.triangulate(false);
+        data.generate_outlines(false);
+        data
+    }
+
+    // Checks that the ranges of the locations cover the buffers in order without gaps
+    fn assert_tiled(data: &TriangulatedData) {
+        let (mut triangles, mut outlines) = (0, 0);
+        for l in 0..data.locs.len() {
+            let fill = data.fill_range(l);
+            assert_eq!(fill[0], triangles);
+            triangles += fill[1];
+            let outline = data.outline_range(l);
+            assert_eq!(outline[0], outlines);
+            outlines += outline[1];
+        }
+        assert_eq!(triangles, data.triangles.len());
+        assert_eq!(outlines, data.outline_triangles.len() / 2);
+    }
+
+    #[test]
+    fn ranges_tile_buffers_after_changes() {
+        let locs = [location([0.0, 0.0], 5), location([0.5, 0.0], 7), location([1.0, 0.0], 9)];
+        let mut data = build(&locs.iter().collect::<Vec<_>>());
+        assert_tiled(&data);
+        data.clear_dirty();
+
+        let replacement = location([0.0, 0.5], 12);
+        data.remove_location(1);
+        data.replace_location(0, &replacement);
+        data.triangulate(false);
+        data.generate_outlines(false);
+        assert_tiled(&data);
+        assert_eq!(data.fill_range(2), vec![0, 0]);
+        for buffer in [RenderBuffer::Vertex, RenderBuffer::Color, RenderBuffer::Triangles, RenderBuffer::Outlines] {
+            assert!(data.dirty_range(buffer).is_some());
+        }
+
+        // The incremental update results in the same buffers as building them from scratch
+        let fresh = build(&[&replacement, &locs[2]]);
+        assert_eq!(data.vertex, fresh.vertex);
+        assert_eq!(data.color, fresh.color);
+        assert_eq!(data.triangles, fresh.triangles);
+        assert_eq!(data.outline_triangles, fresh.outline_triangles);
+        assert_eq!(data.outline_normals, fresh.outline_normals);
+    }
+
+    #[test]
+    fn dirty_range_covers_replaced_location() {
+        let locs = [location([0.0, 0.0], 5), location([0.5, 0.0], 7), location([1.0, 0.0], 9)];
+        let mut data = build(&locs.iter().collect::<Vec<_>>());
+        data.clear_dirty();
+        let vertex = data.vertex.clone();
+
+        // A replacement of the same size only changes its own part of the buffers
+        data.replace_location(1, &location([0.5, 0.5], 7));
+        data.triangulate(false);
+        data.generate_outlines(false);
+        assert_tiled(&data);
+        let dirty = data.dirty_range(RenderBuffer::Vertex).unwrap();
+        assert_eq!(data.vertex.len(), vertex.len());
+        assert!(dirty[1] - dirty[0] < vertex.len());
+        assert_eq!(data.vertex[..dirty[0]], vertex[..dirty[0]]);
+        assert_eq!(data.vertex[dirty[1]..], vertex[dirty[1]..]);
+        let fill = data.fill_range(1);
+        let dirty = data.dirty_range(RenderBuffer::Triangles).unwrap();
+        assert!(dirty[0] <= fill[0] && fill[0] + fill[1] <= dirty[1]);
+    }
+
+    // Asserts that the horizontal extent of the location is `[min, max]`
+    fn assert_extent(loc: &LocationData, min: f32, max: f32) {
+        assert!((loc.min[0] - min).abs() < 1e-4, "{} != {}", loc.min[0], min);
+        assert!((loc.max[0] - max).abs() < 1e-4, "{} != {}", loc.max[0], max);
+    }
+
+    #[test]
+    fn union_merges_overlapping_locations() {
+        let (a, b) = (location([0.0, 0.0], 64), location([0.1, 0.0], 48));
+        let union = a.union(&b, 0.0);
+        assert_eq!(union.id, a.id);
+        assert_eq!(union.name, a.name);
+        assert_eq!(union.polygons.len(), 1);
+        assert_extent(&union, -0.1, 0.2);
+    }
+
+    #[test]
+    fn intersection_keeps_shared_area() {
+        let (a, b) = (location([0.0, 0.0], 64), location([0.1, 0.0], 48));
+        let intersection = a.intersection(&b, 0.0);
+        assert_eq!(intersection.polygons.len(), 1);
+        assert_extent(&intersection, 0.0, 0.1);
+    }
+
+    #[test]
+    fn difference_removes_other_location() {
+        let (a, b) = (location([0.0, 0.0], 64), location([0.1, 0.0], 48));
+        let difference = a.difference(&b, 0.0);
+        assert_eq!(difference.polygons.len(), 1);
+        // The circles cross at x = 0.05, the rest of this location is covered by the other one
+        assert_extent(&difference, -0.1, 0.05);
+        assert!(difference.intersection(&b, 0.0).polygons.is_empty());
+    }
+}
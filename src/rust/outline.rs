@@ -0,0 +1,114 @@
+use std::f32::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{Point, Polygon};
+
+// Largest angle between two triangles of a round join
+const ROUND_STEP: f32 = PI / 8.0;
+
+/// How two segments of an outline are connected. Outlines are closed rings, so they have no caps.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineJoin {
+    Miter,
+    Bevel,
+    Round,
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn dot(a: Point, b: Point) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn scale(a: Point, s: f32) -> Point {
+    [a[0] * s, a[1] * s]
+}
+
+/// The unit normal to the right of the direction from `a` to `b`.
+fn normal(a: Point, b: Point) -> Point {
+    let d = sub(b, a);
+    let len = dot(d, d).sqrt();
+    [d[1] / len, -d[0] / len]
+}
+
+/// Appends the triangles of the outline of all rings of the polygon. Every vertex has a position
+/// on the ring and a normal, which the stroke shader multiplies by the line width and adds to the
+/// position. Normals are unit length, except at miter joins, where they are lengthened so that
+/// the edges of neighbouring segments meet. Miter joins longer than `miter_limit` times the width
+/// are beveled instead.
+pub fn outline_into(poly: &Polygon, join: LineJoin, miter_limit: f32, triangles: &mut Vec<f32>, normals: &mut Vec<f32>) {
+    let mut push = |pos: Point, normal: Point| {
+        triangles.extend(pos);
+        normals.extend(normal);
+    };
+    for ring in poly.rings() {
+        let mut points: Vec<Point> = ring.chunks(2).map(|p| [p[0], p[1]]).collect();
+        points.dedup();
+        while points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        let len = points.len();
+        if len < 2 {
+            continue;
+        }
+        // Normal of the segment starting at every vertex
+        let segments: Vec<Point> = (0..len).map(|i| normal(points[i], points[(i + 1) % len])).collect();
+        // Normals at the end of the incoming and the start of the outgoing segment of every vertex
+        let mut ends = vec![[0.0; 2]; len];
+        let mut starts = vec![[0.0; 2]; len];
+        for i in 0..len {
+            let curr = points[i];
+            let n0 = segments[(len + i - 1) % len];
+            let n1 = segments[i];
+            let miter = scale([n0[0] + n1[0], n0[1] + n1[1]], 1.0 / (1.0 + dot(n0, n1)));
+            if join == LineJoin::Miter && dot(miter, miter) <= miter_limit * miter_limit {
+                ends[i] = miter;
+                starts[i] = miter;
+                continue;
+            }
+            ends[i] = n0;
+            starts[i] = n1;
+            // The join only fills the gap on the outer side of the turn
+            let side = if n0[0] * n1[1] - n0[1] * n1[0] > 0.0 { 1.0 } else { -1.0 };
+            let from = scale(n0, side);
+            let to = scale(n1, side);
+            if join == LineJoin::Round {
+                let angle = dot(n0, n1).clamp(-1.0, 1.0).acos();
+                let steps = (angle / ROUND_STEP).ceil().max(1.0) as usize;
+                let turn = angle.copysign(from[0] * to[1] - from[1] * to[0]) / steps as f32;
+                let mut last = from;
+                for k in 1..=steps {
+                    let next = if k == steps {
+                        to
+                    } else {
+                        let (sin, cos) = (turn * k as f32).sin_cos();
+                        [from[0] * cos - from[1] * sin, from[0] * sin + from[1] * cos]
+                    };
+                    push(curr, [0.0, 0.0]);
+                    push(curr, last);
+                    push(curr, next);
+                    last = next;
+                }
+            } else {
+                push(curr, [0.0, 0.0]);
+                push(curr, from);
+                push(curr, to);
+            }
+        }
+        for i in 0..len {
+            let j = (i + 1) % len;
+            let (a, b) = (starts[i], ends[j]);
+            let neg = |n: Point| [-n[0], -n[1]];
+            push(points[i], a);
+            push(points[i], neg(a));
+            push(points[j], b);
+            push(points[i], neg(a));
+            push(points[j], neg(b));
+            push(points[j], b);
+        }
+    }
+}
@@ -0,0 +1,800 @@
+use std::f32::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{Point, Polygon};
+
+// The latitude at which web mercator becomes square
+const MERCATOR_LATITUDE: f32 = 85.05113 * PI / 180.0;
+
+const ALBERS_PARALLELS: [f32; 2] = [29.5 * PI / 180.0, 45.5 * PI / 180.0];
+const LAMBERT_PARALLELS: [f32; 2] = [33.0 * PI / 180.0, 45.0 * PI / 180.0];
+
+// Maximum latitude difference between the points inserted along the horizon of the orthographic
+// projection
+const HORIZON_STEP: f32 = 5.0 * PI / 180.0;
+
+const EQUAL_EARTH: [f32; 4] = [1.340264, -0.081106, 0.000893, 0.003796];
+
+// Length of the parallels and distance from the equator at every 5 degrees of latitude
+const ROBINSON: [[f32; 2]; 19] = [
+    [1.0000, 0.0000], [0.9986, 0.0620], [0.9954, 0.1240], [0.9900, 0.1860], [0.9822, 0.2480],
+    [0.9730, 0.3100], [0.9600, 0.3720], [0.9427, 0.4340], [0.9216, 0.4958], [0.8962, 0.5571],
+    [0.8679, 0.6176], [0.8350, 0.6769], [0.7986, 0.7346], [0.7597, 0.7903], [0.7186, 0.8435],
+    [0.6732, 0.8936], [0.6213, 0.9394], [0.5722, 0.9761], [0.5322, 1.0000],
+];
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProjectionKind {
+    Equirectangular,
+    WebMercator,
+    EqualEarth,
+    Robinson,
+    AlbersEqualArea,
+    LambertConformalConic,
+    Orthographic,
+}
+
+/// A region of the map that is moved into an inset, e.g. for outlying territories.
+#[derive(Clone, PartialEq, Debug)]
+struct Inset {
+    min: Point,
+    max: Point,
+    location: Option<String>,
+    scale: f32,
+    offset: Point,
+}
+
+/// A map projection. Projected coordinates are offset by `PI` and have the y axis pointing
+/// downwards, so that the map covers roughly the range from `0` to `2 * PI`. The globe is rotated
+/// before projecting it and only latitudes between the latitude bounds of the rotated globe are
+/// projected, polygons are cut at the antimeridian and clipped at these bounds. Polygons inside
+/// of an inset are projected like all others and then scaled and moved as a whole.
+#[wasm_bindgen]
+#[derive(Clone, PartialEq, Debug)]
+pub struct Projection {
+    kind: ProjectionKind,
+    lat_bounds: [f32; 2],
+    rotation: [f32; 3],
+    parallels: [f32; 2],
+    scale: f32,
+    insets: Vec<Inset>,
+}
+
+impl Default for Projection {
+    fn default() -> Projection {
+        Projection::new(ProjectionKind::WebMercator)
+    }
+}
+
+#[wasm_bindgen]
+impl Projection {
+    #[wasm_bindgen]
+    pub fn new(kind: ProjectionKind) -> Projection {
+        let lat_bounds = match kind {
+            ProjectionKind::WebMercator => [-MERCATOR_LATITUDE, MERCATOR_LATITUDE],
+            _ => [-PI / 2.0, PI / 2.0],
+        };
+        let parallels = match kind {
+            ProjectionKind::LambertConformalConic => LAMBERT_PARALLELS,
+            _ => ALBERS_PARALLELS,
+        };
+        Projection { kind, lat_bounds, rotation: [0.0; 3], parallels, scale: 1.0, insets: Vec::new() }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> ProjectionKind {
+        self.kind
+    }
+
+    /// The minimum and maximum latitude in degrees.
+    #[wasm_bindgen(getter)]
+    pub fn latitude_bounds(&self) -> Vec<f32> {
+        self.lat_bounds.iter().map(|lat| lat * 180.0 / PI).collect()
+    }
+
+    #[wasm_bindgen]
+    pub fn set_latitude_bounds(&mut self, min: f32, max: f32) {
+        let min = (min * PI / 180.0).clamp(-PI / 2.0, PI / 2.0);
+        let max = (max * PI / 180.0).clamp(-PI / 2.0, PI / 2.0);
+        self.lat_bounds = [min.min(max), max.max(min)];
+    }
+
+    /// The rotation around the polar axis, the y axis and the x axis in degrees.
+    #[wasm_bindgen(getter)]
+    pub fn rotation(&self) -> Vec<f32> {
+        self.rotation.iter().map(|angle| angle * 180.0 / PI).collect()
+    }
+
+    /// Sets the rotation of the globe in degrees. The globe is first rotated by `lambda` around
+    /// the polar axis, then by `phi` around the y axis and lastly by `gamma` around the x axis.
+    #[wasm_bindgen]
+    pub fn set_rotation(&mut self, lambda: f32, phi: f32, gamma: f32) {
+        self.rotation = [lambda * PI / 180.0, phi * PI / 180.0, gamma * PI / 180.0];
+    }
+
+    /// Centers the map at the given longitude in degrees. This is the same as a rotation of
+    /// `-lon` around the polar axis.
+    #[wasm_bindgen]
+    pub fn set_central_meridian(&mut self, lon: f32) {
+        self.rotation[0] = -lon * PI / 180.0;
+    }
+
+    /// The standard parallels of the conic projections in degrees.
+    #[wasm_bindgen(getter)]
+    pub fn parallels(&self) -> Vec<f32> {
+        self.parallels.iter().map(|lat| lat * 180.0 / PI).collect()
+    }
+
+    #[wasm_bindgen]
+    pub fn set_parallels(&mut self, first: f32, second: f32) {
+        self.parallels = [first * PI / 180.0, second * PI / 180.0];
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    #[wasm_bindgen]
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    /// Adds an inset for the polygons whose center lies inside of the given bounds in degrees and
+    /// returns its index. Insets added later take precedence over earlier ones. The inset setters
+    /// ignore indices of insets that do not exist.
+    #[wasm_bindgen]
+    pub fn add_inset(&mut self, min_lon: f32, min_lat: f32, max_lon: f32, max_lat: f32) -> usize {
+        self.insets.push(Inset {
+            min: [min_lon.min(max_lon) * PI / 180.0, min_lat.min(max_lat) * PI / 180.0],
+            max: [max_lon.max(min_lon) * PI / 180.0, max_lat.max(min_lat) * PI / 180.0],
+            location: None, scale: 1.0, offset: [0.0, 0.0],
+        });
+        self.insets.len() - 1
+    }
+
+    /// Moves all polygons of the location with the given id into the inset instead of the ones
+    /// inside of its bounds. The bounds should still cover the location for the inverse projection.
+    #[wasm_bindgen]
+    pub fn set_inset_location(&mut self, i: usize, id: Option<String>) {
+        if let Some(inset) = self.insets.get_mut(i) {
+            inset.location = id;
+        }
+    }
+
+    /// Sets the scale of the inset relative to the rest of the map.
+    #[wasm_bindgen]
+    pub fn set_inset_scale(&mut self, i: usize, scale: f32) {
+        if let Some(inset) = self.insets.get_mut(i) {
+            inset.scale = scale;
+        }
+    }
+
+    /// Moves the center of the inset bounds by the given longitude and latitude in degrees.
+    #[wasm_bindgen]
+    pub fn set_inset_offset(&mut self, i: usize, lon: f32, lat: f32) {
+        if let Some(inset) = self.insets.get_mut(i) {
+            inset.offset = [lon * PI / 180.0, lat * PI / 180.0];
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn count_insets(&self) -> usize {
+        self.insets.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_insets(&mut self) {
+        self.insets.clear();
+    }
+
+    /// Projects the given longitude and latitude in degrees.
+    #[wasm_bindgen]
+    pub fn project_point(&self, lon: f32, lat: f32) -> Vec<f32> {
+        self.forward(lon * PI / 180.0, lat * PI / 180.0).to_vec()
+    }
+
+    /// Computes the longitude and latitude in degrees of the given projected coordinates, or
+    /// returns `undefined` if they are outside of the map.
+    #[wasm_bindgen]
+    pub fn invert_point(&self, x: f32, y: f32) -> Option<Vec<f32>> {
+        self.inverse([x, y]).map(|pos| vec![pos[0] * 180.0 / PI, pos[1] * 180.0 / PI])
+    }
+}
+
+impl Projection {
+    /// Identifies the projection by the bits of all of its parameters. Unlike `==` this also
+    /// matches parameters that are NaN, so it can be used to look up cached geometry.
+    pub fn key(&self) -> Vec<u32> {
+        let mut key = vec![self.kind as u32];
+        let params = self.lat_bounds.iter().chain(&self.rotation).chain(&self.parallels).chain([&self.scale]);
+        key.extend(params.map(|param| param.to_bits()));
+        for inset in &self.insets {
+            let params = inset.min.iter().chain(&inset.max).chain([&inset.scale]).chain(&inset.offset);
+            key.extend(params.map(|param| param.to_bits()));
+            // The length is stored first so that the keys of different insets cannot be confused
+            match &inset.location {
+                Some(location) => {
+                    key.push(location.len() as u32 + 1);
+                    key.extend(location.bytes().map(u32::from));
+                }
+                None => key.push(0),
+            }
+        }
+        key
+    }
+
+    /// Projects the given longitude and latitude, both in radians.
+    pub fn forward(&self, lon: f32, lat: f32) -> Point {
+        let [lon, lat] = self.rotate(lon, lat);
+        self.project(lon, lat)
+    }
+
+    /// Computes the longitude and latitude, in radians, of the given projected coordinates.
+    /// Returns `None` if the coordinates are outside of the area covered by the projection.
+    /// Coordinates inside of an inset are mapped back into the bounds of that inset.
+    pub fn inverse(&self, pos: Point) -> Option<Point> {
+        for inset in self.insets.iter().rev() {
+            let [center, target] = self.inset_anchors(inset);
+            let pos = [
+                center[0] + (pos[0] - target[0]) / inset.scale,
+                center[1] + (pos[1] - target[1]) / inset.scale,
+            ];
+            if let Some(loc) = self.unproject(pos).map(|[lon, lat]| self.unrotate(lon, lat)) {
+                if (0..2).all(|d| inset.min[d] <= loc[d] && loc[d] <= inset.max[d]) {
+                    return Some(loc);
+                }
+            }
+        }
+        self.unproject(pos).map(|[lon, lat]| self.unrotate(lon, lat))
+    }
+
+    /// Projects a polygon of the location with the given id, moving it into the inset it belongs to
+    /// if there is one.
+    pub fn project_polygon(&self, location: &str, poly: &Polygon) -> Vec<Polygon> {
+        let mut polys = poly.projected(self);
+        if let Some(inset) = self.inset_for(location, poly) {
+            let [center, target] = self.inset_anchors(inset);
+            for poly in &mut polys {
+                for i in (0..poly.vertex.len()).step_by(2) {
+                    poly.vertex[i] = target[0] + (poly.vertex[i] - center[0]) * inset.scale;
+                    poly.vertex[i + 1] = target[1] + (poly.vertex[i + 1] - center[1]) * inset.scale;
+                }
+                poly.min = [0, 1].map(|d| target[d] + (poly.min[d] - center[d]) * inset.scale);
+                poly.max = [0, 1].map(|d| target[d] + (poly.max[d] - center[d]) * inset.scale);
+            }
+        }
+        polys
+    }
+
+    fn inset_for(&self, location: &str, poly: &Polygon) -> Option<&Inset> {
+        let center = [0, 1].map(|d| (poly.min[d] + poly.max[d]) / 2.0);
+        self.insets.iter().rev().find(|inset| match &inset.location {
+            Some(id) => id == location,
+            None => (0..2).all(|d| inset.min[d] <= center[d] && center[d] <= inset.max[d]),
+        })
+    }
+
+    /// The projected center of the inset bounds and the position it is moved to.
+    fn inset_anchors(&self, inset: &Inset) -> [Point; 2] {
+        let center = [0, 1].map(|d| (inset.min[d] + inset.max[d]) / 2.0);
+        [
+            self.forward(center[0], center[1]),
+            self.forward(center[0] + inset.offset[0], center[1] + inset.offset[1]),
+        ]
+    }
+
+    fn is_rotated(&self) -> bool {
+        self.rotation != [0.0; 3]
+    }
+
+    fn rotate(&self, lon: f32, lat: f32) -> Point {
+        let [lambda, phi, gamma] = self.rotation;
+        let lon = wrap_longitude(lon + lambda);
+        if phi == 0.0 && gamma == 0.0 {
+            [lon, lat]
+        } else {
+            let [x, y, z] = [lon.cos() * lat.cos(), lon.sin() * lat.cos(), lat.sin()];
+            let k = z * phi.cos() + x * phi.sin();
+            [
+                f32::atan2(y * gamma.cos() - k * gamma.sin(), x * phi.cos() - z * phi.sin()),
+                f32::asin((k * gamma.cos() + y * gamma.sin()).clamp(-1.0, 1.0)),
+            ]
+        }
+    }
+
+    fn unrotate(&self, lon: f32, lat: f32) -> Point {
+        let [lambda, phi, gamma] = self.rotation;
+        let [lon, lat] = if phi == 0.0 && gamma == 0.0 {
+            [lon, lat]
+        } else {
+            let [x, y, z] = [lon.cos() * lat.cos(), lon.sin() * lat.cos(), lat.sin()];
+            let k = z * gamma.cos() - y * gamma.sin();
+            [
+                f32::atan2(y * gamma.cos() + z * gamma.sin(), x * phi.cos() + k * phi.sin()),
+                f32::asin((k * phi.cos() - x * phi.sin()).clamp(-1.0, 1.0)),
+            ]
+        };
+        [wrap_longitude(lon - lambda), lat]
+    }
+
+    // Projects coordinates of the rotated globe.
+    fn project(&self, lon: f32, lat: f32) -> Point {
+        let lat = lat.clamp(self.lat_bounds[0], self.lat_bounds[1]);
+        let [x, y] = match self.kind {
+            ProjectionKind::Equirectangular => [lon, lat],
+            ProjectionKind::WebMercator => {
+                // The poles are at infinity
+                let lat = lat.clamp(-PI / 2.0 + 1e-3, PI / 2.0 - 1e-3);
+                [lon, f32::ln(f32::tan(PI / 4.0 + lat / 2.0))]
+            }
+            ProjectionKind::EqualEarth => {
+                let [a1, a2, a3, a4] = EQUAL_EARTH;
+                let m = f32::sqrt(3.0) / 2.0;
+                let t = f32::asin(m * lat.sin());
+                let t2 = t * t;
+                let t6 = t2 * t2 * t2;
+                [
+                    lon * t.cos() / (m * (a1 + 3.0 * a2 * t2 + t6 * (7.0 * a3 + 9.0 * a4 * t2))),
+                    t * (a1 + a2 * t2 + t6 * (a3 + a4 * t2)),
+                ]
+            }
+            ProjectionKind::Robinson => {
+                let [len, dist] = robinson_row(lat.abs());
+                [0.8487 * len * lon, 1.3523 * dist * lat.signum()]
+            }
+            ProjectionKind::AlbersEqualArea => {
+                let (n, c, rho0) = albers_constants(self.parallels);
+                if n.abs() < 1e-6 {
+                    // Degenerates to a cylindrical equal-area projection
+                    let cos = self.parallels[0].cos();
+                    [lon * cos, lat.sin() / cos]
+                } else {
+                    let rho = f32::sqrt((c - 2.0 * n * lat.sin()).max(0.0)) / n;
+                    [rho * f32::sin(n * lon), rho0 - rho * f32::cos(n * lon)]
+                }
+            }
+            ProjectionKind::LambertConformalConic => {
+                let (n, f) = lambert_constants(self.parallels);
+                // The pole opposite to the cone apex is at infinity
+                let lat = lat.clamp(-PI / 2.0 + 1e-3, PI / 2.0 - 1e-3);
+                if n.abs() < 1e-6 {
+                    // Degenerates to the mercator projection
+                    [lon, f32::ln(f32::tan(PI / 4.0 + lat / 2.0))]
+                } else {
+                    let rho = f / f32::tan(PI / 4.0 + lat / 2.0).powf(n);
+                    [rho * f32::sin(n * lon), f - rho * f32::cos(n * lon)]
+                }
+            }
+            ProjectionKind::Orthographic => {
+                let pos = [lat.cos() * lon.sin(), lat.sin()];
+                if lat.cos() * lon.cos() < 0.0 {
+                    // Polygons are clipped at the horizon, single points on the far side of the
+                    // globe are moved onto it
+                    let len = f32::hypot(pos[0], pos[1]);
+                    if len > 0.0 { [pos[0] / len, pos[1] / len] } else { [1.0, 0.0] }
+                } else {
+                    pos
+                }
+            }
+        };
+        [PI + self.scale * x, PI - self.scale * y]
+    }
+
+    // Computes coordinates on the rotated globe.
+    fn unproject(&self, pos: Point) -> Option<Point> {
+        let (x, y) = ((pos[0] - PI) / self.scale, (PI - pos[1]) / self.scale);
+        let [lon, lat] = match self.kind {
+            ProjectionKind::Equirectangular => [x, y],
+            ProjectionKind::WebMercator => [x, 2.0 * f32::atan(y.exp()) - PI / 2.0],
+            ProjectionKind::EqualEarth => {
+                let [a1, a2, a3, a4] = EQUAL_EARTH;
+                let m = f32::sqrt(3.0) / 2.0;
+                let mut t = y;
+                let mut dy = 0.0;
+                for _ in 0..12 {
+                    let t2 = t * t;
+                    let t6 = t2 * t2 * t2;
+                    dy = a1 + 3.0 * a2 * t2 + t6 * (7.0 * a3 + 9.0 * a4 * t2);
+                    let delta = (t * (a1 + a2 * t2 + t6 * (a3 + a4 * t2)) - y) / dy;
+                    t -= delta;
+                    if delta.abs() < 1e-7 {
+                        break;
+                    }
+                }
+                let sin = t.sin() / m;
+                if sin.abs() > 1.0 {
+                    return None;
+                }
+                [x * m * dy / t.cos(), sin.asin()]
+            }
+            ProjectionKind::Robinson => {
+                let dist = y.abs() / 1.3523;
+                if dist > 1.0 {
+                    return None;
+                }
+                let i = ROBINSON.partition_point(|row| row[1] < dist).clamp(1, ROBINSON.len() - 1);
+                let t = (dist - ROBINSON[i - 1][1]) / (ROBINSON[i][1] - ROBINSON[i - 1][1]);
+                let lat = (i as f32 - 1.0 + t) * 5.0 * PI / 180.0;
+                [x / (0.8487 * robinson_row(lat)[0]), lat * y.signum()]
+            }
+            ProjectionKind::AlbersEqualArea => {
+                let (n, c, rho0) = albers_constants(self.parallels);
+                if n.abs() < 1e-6 {
+                    let cos = self.parallels[0].cos();
+                    if (y * cos).abs() > 1.0 {
+                        return None;
+                    }
+                    [x / cos, f32::asin(y * cos)]
+                } else {
+                    let rho = n.signum() * f32::hypot(x, rho0 - y);
+                    let sin = (c - rho * rho * n * n) / (2.0 * n);
+                    if sin.abs() > 1.0 {
+                        return None;
+                    }
+                    let theta = f32::atan2(n.signum() * x, n.signum() * (rho0 - y));
+                    [theta / n, sin.asin()]
+                }
+            }
+            ProjectionKind::LambertConformalConic => {
+                let (n, f) = lambert_constants(self.parallels);
+                if n.abs() < 1e-6 {
+                    [x, 2.0 * f32::atan(y.exp()) - PI / 2.0]
+                } else {
+                    let rho = n.signum() * f32::hypot(x, f - y);
+                    let theta = f32::atan2(n.signum() * x, n.signum() * (f - y));
+                    [theta / n, 2.0 * f32::atan((f / rho).powf(1.0 / n)) - PI / 2.0]
+                }
+            }
+            ProjectionKind::Orthographic => {
+                let rho2 = x * x + y * y;
+                // Points clipped at the horizon can be slightly outside due to rounding
+                if rho2 > 1.0 + 1e-5 {
+                    return None;
+                }
+                [f32::atan2(x, f32::sqrt((1.0 - rho2).max(0.0))), y.clamp(-1.0, 1.0).asin()]
+            }
+        };
+        if lon.abs() > PI + 1e-5 || !lon.is_finite() || !lat.is_finite()
+            || lat < self.lat_bounds[0] - 1e-5 || lat > self.lat_bounds[1] + 1e-5
+        {
+            None
+        } else {
+            Some([lon, lat])
+        }
+    }
+
+    /// Tests whether the given latitude range lies completely within the latitude bounds.
+    fn covers(&self, min: f32, max: f32) -> bool {
+        min >= self.lat_bounds[0] && max <= self.lat_bounds[1]
+    }
+
+    // Cuts the polygon, given as rings on the rotated globe, where it crosses the antimeridian
+    // and clips the parts at the latitude bounds.
+    fn cut(&self, rings: Vec<Vec<f32>>) -> Vec<Vec<Vec<f32>>> {
+        let mut rings: Vec<Vec<f32>> = rings.into_iter().map(|ring| {
+            let ring = close_around_pole(ring);
+            let ring = clip_ring(&ring, 1, self.lat_bounds[0], true);
+            clip_ring(&ring, 1, self.lat_bounds[1], false)
+        }).collect();
+        if is_sliver(&rings[0]) {
+            return Vec::new();
+        }
+        let (min, max) = rings[0].chunks(2).fold((f32::MAX, f32::MIN), |(min, max), p| (min.min(p[0]), max.max(p[0])));
+        // Holes must be continuous with the outline
+        for ring in rings.iter_mut().skip(1).filter(|ring| !ring.is_empty()) {
+            let shift = ((min + max) / 2.0 - ring[0]) / (2.0 * PI);
+            let shift = shift.round() * 2.0 * PI;
+            ring.iter_mut().step_by(2).for_each(|lon| *lon += shift);
+        }
+        let first = ((min + PI) / (2.0 * PI)).floor() as i32;
+        let last = ((max - PI) / (2.0 * PI)).ceil() as i32;
+        let mut parts = Vec::new();
+        for k in first..=last {
+            let offset = 2.0 * PI * k as f32;
+            let mut part = Vec::new();
+            for (j, ring) in rings.iter().enumerate() {
+                let mut ring = if k == 0 && min >= -PI && max <= PI {
+                    ring.clone()
+                } else {
+                    clip_ring(&clip_ring(ring, 0, offset - PI, true), 0, offset + PI, false)
+                };
+                if is_sliver(&ring) {
+                    if j == 0 {
+                        break;
+                    }
+                } else {
+                    ring.iter_mut().step_by(2).for_each(|lon| *lon -= offset);
+                    part.push(ring);
+                }
+            }
+            if !part.is_empty() {
+                parts.push(part);
+            }
+        }
+        parts
+    }
+}
+
+impl Polygon {
+    /// Projects the polygon. Because the polygon is cut at the antimeridian and clipped at the
+    /// latitude bounds, and at the horizon for the orthographic projection, this can result in any
+    /// number of polygons.
+    pub fn projected(&self, projection: &Projection) -> Vec<Polygon> {
+        let parts = if !projection.is_rotated() && projection.covers(self.min[1], self.max[1]) {
+            vec![self.rings().map(|ring| ring.to_vec()).collect()]
+        } else {
+            let rings = self.rings().map(|ring| {
+                let len = ring.len() / 2;
+                let mut rotated = Vec::with_capacity(ring.len());
+                for i in 0..len {
+                    let j = (i + 1) % len;
+                    let (a, b) = ([ring[2 * i], ring[2 * i + 1]], [ring[2 * j], ring[2 * j + 1]]);
+                    // Edges along the poles or the antimeridian would collapse when rotated. Edges
+                    // from one side of the antimeridian to the other follow the parallel around the
+                    // globe, all other edges take the short way across the antimeridian.
+                    let delta = if a[0].abs() == PI && b[0].abs() == PI {
+                        b[0] - a[0]
+                    } else {
+                        wrap_longitude(b[0] - a[0])
+                    };
+                    let steps = (delta.abs() / (PI / 2.0)).ceil().max(1.0) as usize;
+                    for k in 0..steps {
+                        let t = k as f32 / steps as f32;
+                        rotated.extend(projection.rotate(a[0] + t * delta, a[1] + t * (b[1] - a[1])));
+                    }
+                }
+                rotated
+            }).collect();
+            projection.cut(rings)
+        };
+        let parts = if projection.kind == ProjectionKind::Orthographic {
+            parts.into_iter().filter_map(clip_horizon).collect()
+        } else {
+            parts
+        };
+        parts.into_iter().map(|rings| {
+            let mut poly = Polygon {
+                vertex: Vec::new(), holes: Vec::new(),
+                min: [f32::MAX, f32::MAX], max: [f32::MIN, f32::MIN]
+            };
+            for (j, ring) in rings.into_iter().enumerate() {
+                if j != 0 {
+                    poly.holes.push((poly.vertex.len() / 2) as u32);
+                }
+                poly.vertex.extend(ring);
+            }
+            for i in (0..poly.vertex.len()).step_by(2) {
+                let proj = projection.project(poly.vertex[i], poly.vertex[i + 1]);
+                poly.vertex[i] = proj[0];
+                poly.vertex[i + 1] = proj[1];
+                poly.min[0] = poly.min[0].min(proj[0]);
+                poly.min[1] = poly.min[1].min(proj[1]);
+                poly.max[0] = poly.max[0].max(proj[0]);
+                poly.max[1] = poly.max[1].max(proj[1]);
+            }
+            poly
+        }).collect()
+    }
+}
+
+fn wrap_longitude(lon: f32) -> f32 {
+    if lon > PI {
+        lon - 2.0 * PI
+    } else if lon < -PI {
+        lon + 2.0 * PI
+    } else {
+        lon
+    }
+}
+
+// Makes the longitudes of the ring continuous. If the ring goes around a pole, it is closed along
+// the pole, which is assumed to be the one on the side of the ring.
+fn close_around_pole(mut ring: Vec<f32>) -> Vec<f32> {
+    let len = ring.len() / 2;
+    if len == 0 {
+        return ring;
+    }
+    let mut offset = 0.0;
+    for i in 1..len {
+        let lon = ring[2 * i] + offset;
+        let delta = lon - ring[2 * i - 2];
+        if delta > PI {
+            offset -= 2.0 * PI;
+        } else if delta < -PI {
+            offset += 2.0 * PI;
+        }
+        ring[2 * i] += offset;
+    }
+    let winding = ring[2 * len - 2] - ring[0];
+    if winding.abs() > PI {
+        let lat: f32 = ring.iter().skip(1).step_by(2).sum();
+        let pole = PI / 2.0 * lat.signum();
+        ring.extend([ring[2 * len - 2], pole, ring[0], pole]);
+    }
+    ring
+}
+
+// Clips the ring at `bound` in dimension `d`, keeping the part above or below of it.
+fn clip_ring(ring: &[f32], d: usize, bound: f32, above: bool) -> Vec<f32> {
+    let inside = |p: &[f32]| if above { p[d] >= bound } else { p[d] <= bound };
+    let len = ring.len() / 2;
+    let mut clipped = Vec::with_capacity(ring.len());
+    for i in 0..len {
+        let last = &ring[2 * ((i + len - 1) % len)..2 * ((i + len - 1) % len) + 2];
+        let cur = &ring[2 * i..2 * i + 2];
+        if inside(cur) != inside(last) {
+            let t = (bound - last[d]) / (cur[d] - last[d]);
+            let mut point = [last[0] + t * (cur[0] - last[0]), last[1] + t * (cur[1] - last[1])];
+            point[d] = bound;
+            clipped.extend(point);
+        }
+        if inside(cur) {
+            clipped.extend(cur);
+        }
+    }
+    clipped
+}
+
+// Clips the polygon, given as rings on the rotated globe, at the horizon of the orthographic
+// projection, so that only the hemisphere facing the viewer remains. The horizon runs along the
+// meridians at 90 degrees from the center, the clipped edges are subdivided to follow its curve.
+fn clip_horizon(rings: Vec<Vec<f32>>) -> Option<Vec<Vec<f32>>> {
+    let mut part = Vec::new();
+    for (j, ring) in rings.iter().enumerate() {
+        let ring = clip_ring(&clip_ring(ring, 0, -PI / 2.0, true), 0, PI / 2.0, false);
+        if is_sliver(&ring) {
+            if j == 0 {
+                return None;
+            }
+        } else {
+            part.push(follow_horizon(&ring));
+        }
+    }
+    Some(part)
+}
+
+// Inserts points along the edges of the ring that lie on the horizon.
+fn follow_horizon(ring: &[f32]) -> Vec<f32> {
+    let len = ring.len() / 2;
+    let mut curved = Vec::with_capacity(ring.len());
+    for i in 0..len {
+        let j = (i + 1) % len;
+        let (a, b) = ([ring[2 * i], ring[2 * i + 1]], [ring[2 * j], ring[2 * j + 1]]);
+        curved.extend(a);
+        if a[0] == b[0] && a[0].abs() == PI / 2.0 {
+            let steps = ((b[1] - a[1]).abs() / HORIZON_STEP).ceil() as usize;
+            for k in 1..steps {
+                curved.extend([a[0], a[1] + k as f32 / steps as f32 * (b[1] - a[1])]);
+            }
+        }
+    }
+    curved
+}
+
+// Tests whether the ring is empty or has been reduced to a sliver along the clipping boundary.
+fn is_sliver(ring: &[f32]) -> bool {
+    let len = ring.len() / 2;
+    let (mut area, mut perimeter) = (0.0, 0.0);
+    for i in 0..len {
+        let j = (i + 1) % len;
+        let a = [ring[2 * i] - ring[0], ring[2 * i + 1] - ring[1]];
+        let b = [ring[2 * j] - ring[0], ring[2 * j + 1] - ring[1]];
+        area += a[0] * b[1] - b[0] * a[1];
+        perimeter += f32::hypot(b[0] - a[0], b[1] - a[1]);
+    }
+    len < 3 || area.abs() <= 1e-6 * perimeter * perimeter
+}
+
+// Interpolates the Robinson table at the given latitude.
+fn robinson_row(lat: f32) -> [f32; 2] {
+    let pos = (lat.abs() * 180.0 / PI / 5.0).min((ROBINSON.len() - 1) as f32);
+    let i = (pos as usize).min(ROBINSON.len() - 2);
+    let t = pos - i as f32;
+    [
+        ROBINSON[i][0] + t * (ROBINSON[i + 1][0] - ROBINSON[i][0]),
+        ROBINSON[i][1] + t * (ROBINSON[i + 1][1] - ROBINSON[i][1]),
+    ]
+}
+
+fn albers_constants(parallels: [f32; 2]) -> (f32, f32, f32) {
+    let n = (parallels[0].sin() + parallels[1].sin()) / 2.0;
+    let c = parallels[0].cos().powi(2) + 2.0 * n * parallels[0].sin();
+    (n, c, c.sqrt() / n)
+}
+
+fn lambert_constants(parallels: [f32; 2]) -> (f32, f32) {
+    let [p1, p2] = parallels;
+    let n = if (p1 - p2).abs() < 1e-6 {
+        p1.sin()
+    } else {
+        f32::ln(p1.cos() / p2.cos()) / f32::ln(f32::tan(PI / 4.0 + p2 / 2.0) / f32::tan(PI / 4.0 + p1 / 2.0))
+    };
+    (n, p1.cos() * f32::tan(PI / 4.0 + p1 / 2.0).powf(n) / n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [ProjectionKind; 7] = [
+        ProjectionKind::Equirectangular, ProjectionKind::WebMercator, ProjectionKind::EqualEarth,
+        ProjectionKind::Robinson, ProjectionKind::AlbersEqualArea, ProjectionKind::LambertConformalConic,
+        ProjectionKind::Orthographic,
+    ];
+
+    // Checks all points with longitude and latitude up to `extent` degrees from the origin
+    fn assert_round_trip(projection: &Projection, extent: i32) {
+        for lon in (-extent..=extent).step_by(15) {
+            for lat in (-extent..=extent).step_by(15) {
+                let [x, y] = projection.project_point(lon as f32, lat as f32)[..] else { unreachable!() };
+                let inverse = projection.invert_point(x, y)
+                    .unwrap_or_else(|| panic!("{:?} cannot invert {} {}", projection.kind, lon, lat));
+                assert!(
+                    (inverse[0] - lon as f32).abs() < 1e-2 && (inverse[1] - lat as f32).abs() < 1e-2,
+                    "{:?} maps {} {} to {:?}", projection.kind, lon, lat, inverse
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_of_every_kind() {
+        for kind in KINDS {
+            assert_round_trip(&Projection::new(kind), 60);
+        }
+    }
+
+    #[test]
+    fn inverse_with_parameters() {
+        for kind in KINDS {
+            let mut projection = Projection::new(kind);
+            projection.set_rotation(10.0, -20.0, 5.0);
+            projection.set_parallels(20.0, 50.0);
+            projection.set_scale(2.0);
+            // The rotation moves the far points of the orthographic projection to the back side
+            assert_round_trip(&projection, 30);
+        }
+    }
+
+    fn square(min: Point, max: Point) -> Polygon {
+        let deg = PI / 180.0;
+        Polygon {
+            vertex: [min[0], min[1], max[0], min[1], max[0], max[1], min[0], max[1]].map(|c| c * deg).to_vec(),
+            holes: Vec::new(),
+            min: [min[0].min(max[0]) * deg, min[1] * deg], max: [max[0].max(min[0]) * deg, max[1] * deg],
+        }
+    }
+
+    #[test]
+    fn orthographic_clips_at_the_horizon() {
+        let projection = Projection::new(ProjectionKind::Orthographic);
+        assert!(square([120.0, -10.0], [150.0, 10.0]).projected(&projection).is_empty());
+        let clipped = square([60.0, -30.0], [120.0, 30.0]).projected(&projection);
+        assert_eq!(clipped.len(), 1);
+        for pos in clipped[0].vertex.chunks(2) {
+            let [lon, lat] = projection.invert_point(pos[0], pos[1]).unwrap()[..] else { unreachable!() };
+            assert!((60.0 - 1e-2..=90.0 + 1e-2).contains(&lon) && lat.abs() <= 30.0 + 1e-2, "{} {}", lon, lat);
+        }
+        // The clipped edge follows the horizon instead of cutting across the disc
+        assert!(clipped[0].vertex.len() > 8);
+        let mut rotated = Projection::new(ProjectionKind::Orthographic);
+        rotated.set_central_meridian(135.0);
+        assert_eq!(square([120.0, -10.0], [150.0, 10.0]).projected(&rotated).len(), 1);
+        assert!(square([-60.0, -10.0], [-30.0, 10.0]).projected(&rotated).is_empty());
+    }
+
+    #[test]
+    fn edges_across_the_antimeridian_take_the_short_way() {
+        let deg = PI / 180.0;
+        let poly = square([179.0, 10.0], [-179.0, 20.0]);
+        let mut projection = Projection::new(ProjectionKind::Equirectangular);
+        projection.set_central_meridian(180.0);
+        let projected = poly.projected(&projection);
+        assert_eq!(projected.len(), 1);
+        let width = projected[0].max[0] - projected[0].min[0];
+        assert!((width - 2.0 * deg).abs() < 1e-3, "width {}", width);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{Point, Polygon};
+
+fn cross(o: Point, a: Point, b: Point) -> f32 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+fn segments_intersect(a0: Point, a1: Point, b0: Point, b1: Point) -> bool {
+    fn on_segment(a: Point, b: Point, p: Point) -> bool {
+        p[0] <= a[0].max(b[0]) && p[0] >= a[0].min(b[0]) && p[1] <= a[1].max(b[1]) && p[1] >= a[1].min(b[1])
+    }
+    let o1 = cross(a0, a1, b0).signum();
+    let o2 = cross(a0, a1, b1).signum();
+    let o3 = cross(b0, b1, a0).signum();
+    let o4 = cross(b0, b1, a1).signum();
+    (o1 != o2 && o3 != o4)
+        || (o1 == 0.0 && on_segment(a0, a1, b0))
+        || (o2 == 0.0 && on_segment(a0, a1, b1))
+        || (o3 == 0.0 && on_segment(b0, b1, a0))
+        || (o4 == 0.0 && on_segment(b0, b1, a1))
+}
+
+/// A closed selection ring, e.g. a box or a freehand lasso. Self intersecting rings are handled
+/// using the even-odd rule.
+pub struct Selection {
+    ring: Vec<Point>,
+    min: Point,
+    max: Point,
+}
+
+impl Selection {
+    pub fn new(ring: Vec<Point>) -> Selection {
+        let mut min = [f32::MAX, f32::MAX];
+        let mut max = [f32::MIN, f32::MIN];
+        for p in &ring {
+            min = [min[0].min(p[0]), min[1].min(p[1])];
+            max = [max[0].max(p[0]), max[1].max(p[1])];
+        }
+        Selection { ring, min, max }
+    }
+
+    pub fn from_box(min: Point, max: Point) -> Selection {
+        Selection::new(vec![min, [max[0], min[1]], max, [min[0], max[1]]])
+    }
+
+    fn contains_point(&self, p: Point) -> bool {
+        let mut inside = false;
+        let mut last = self.ring[self.ring.len() - 1];
+        for &cur in &self.ring {
+            if (cur[1] > p[1]) != (last[1] > p[1])
+                && p[0] < (last[0] - cur[0]) * (p[1] - cur[1]) / (last[1] - cur[1]) + cur[0]
+            {
+                inside = !inside;
+            }
+            last = cur;
+        }
+        inside
+    }
+
+    fn crosses_ring(&self, ring: &[f32]) -> bool {
+        let len = ring.len() / 2;
+        for i in 0..len {
+            let j = (i + 1) % len;
+            let (a0, a1) = ([ring[2 * i], ring[2 * i + 1]], [ring[2 * j], ring[2 * j + 1]]);
+            if a0[0].max(a1[0]) < self.min[0] || a0[0].min(a1[0]) > self.max[0]
+                || a0[1].max(a1[1]) < self.min[1] || a0[1].min(a1[1]) > self.max[1]
+            {
+                continue;
+            }
+            let mut last = self.ring[self.ring.len() - 1];
+            for &cur in &self.ring {
+                if segments_intersect(a0, a1, last, cur) {
+                    return true;
+                }
+                last = cur;
+            }
+        }
+        false
+    }
+
+    fn overlaps_box(&self, min: Point, max: Point) -> bool {
+        min[0] <= self.max[0] && min[1] <= self.max[1] && max[0] >= self.min[0] && max[1] >= self.min[1]
+    }
+
+    fn intersects_polygon(&self, poly: &Polygon) -> bool {
+        self.overlaps_box(poly.min, poly.max) && (
+            self.contains_point([poly.vertex[0], poly.vertex[1]])
+            || self.ring.iter().any(|&p| poly.contains(p, None))
+            || poly.rings().any(|ring| self.crosses_ring(ring))
+        )
+    }
+
+    fn contains_polygon(&self, poly: &Polygon) -> bool {
+        let outline = poly.rings().next().unwrap();
+        poly.min[0] >= self.min[0] && poly.min[1] >= self.min[1]
+            && poly.max[0] <= self.max[0] && poly.max[1] <= self.max[1]
+            && outline.chunks(2).all(|p| self.contains_point([p[0], p[1]]))
+            && !self.crosses_ring(outline)
+    }
+
+    /// Tests whether the polygons of a location touch the selection, or if `contains` is set,
+    /// whether they are completely inside of the selection.
+    pub fn matches(&self, polys: &[Polygon], contains: bool) -> bool {
+        if polys.is_empty() {
+            false
+        } else if contains {
+            polys.iter().all(|poly| self.contains_polygon(poly))
+        } else {
+            polys.iter().any(|poly| self.intersects_polygon(poly))
+        }
+    }
+}
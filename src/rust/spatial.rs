@@ -0,0 +1,166 @@
+use crate::Point;
+
+const NODE_SIZE: usize = 8;
+
+#[derive(Clone, Copy)]
+struct Node {
+    min: Point,
+    max: Point,
+    start: usize,
+    end: usize,
+    leaf: bool,
+}
+
+fn overlaps(amin: Point, amax: Point, bmin: Point, bmax: Point) -> bool {
+    amin[0] <= bmax[0] && amin[1] <= bmax[1] && amax[0] >= bmin[0] && amax[1] >= bmin[1]
+}
+
+/// A static R-tree over axis aligned bounding boxes, bulk loaded using sort-tile-recursive.
+pub struct RTree {
+    nodes: Vec<Node>,
+    items: Vec<usize>,
+}
+
+impl RTree {
+    pub fn new(boxes: &[(Point, Point)]) -> RTree {
+        let mut tree = RTree { nodes: Vec::new(), items: (0..boxes.len()).collect() };
+        if boxes.is_empty() {
+            return tree;
+        }
+        let center = |b: &(Point, Point), i: usize| (b.0[i] + b.1[i]) / 2.0;
+        let mut items = std::mem::take(&mut tree.items);
+        for group in tile(&mut items, |&i, d| center(&boxes[i], d)) {
+            let (min, max) = group.clone().map(|i| boxes[items[i]]).fold(
+                ([f32::MAX, f32::MAX], [f32::MIN, f32::MIN]),
+                |(min, max), b| ([min[0].min(b.0[0]), min[1].min(b.0[1])], [max[0].max(b.1[0]), max[1].max(b.1[1])])
+            );
+            tree.nodes.push(Node { min, max, start: group.start, end: group.end, leaf: true });
+        }
+        tree.items = items;
+        let mut level = 0..tree.nodes.len();
+        while level.len() > 1 {
+            let mut children: Vec<usize> = level.clone().collect();
+            let nodes = &tree.nodes;
+            let groups = tile(&mut children, |&n, d| (nodes[n].min[d] + nodes[n].max[d]) / 2.0);
+            // Children of the same parent must be stored consecutively
+            let mut reordered: Vec<Node> = Vec::with_capacity(children.len());
+            let mut parents = Vec::new();
+            for group in groups {
+                let (mut min, mut max) = ([f32::MAX, f32::MAX], [f32::MIN, f32::MIN]);
+                let start = level.start + reordered.len();
+                for &c in &children[group] {
+                    let node = &tree.nodes[c];
+                    min = [min[0].min(node.min[0]), min[1].min(node.min[1])];
+                    max = [max[0].max(node.max[0]), max[1].max(node.max[1])];
+                    reordered.push(*node);
+                }
+                parents.push(Node { min, max, start, end: level.start + reordered.len(), leaf: false });
+            }
+            tree.nodes.truncate(level.start);
+            tree.nodes.extend(reordered);
+            let start = tree.nodes.len();
+            tree.nodes.extend(parents);
+            level = start..tree.nodes.len();
+        }
+        tree
+    }
+
+    /// Calls `f` for every item whose bounding box overlaps the given box.
+    pub fn search(&self, min: Point, max: Point, mut f: impl FnMut(usize)) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![self.nodes.len() - 1];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if overlaps(node.min, node.max, min, max) {
+                if node.leaf {
+                    self.items[node.start..node.end].iter().for_each(|&i| f(i));
+                } else {
+                    stack.extend(node.start..node.end);
+                }
+            }
+        }
+    }
+}
+
+// Sorts the entries into tiles of at most `NODE_SIZE` entries and returns their ranges.
+fn tile<T, F: Fn(&T, usize) -> f32>(entries: &mut [T], coord: F) -> Vec<std::ops::Range<usize>> {
+    let groups = entries.len().div_ceil(NODE_SIZE);
+    let slices = (groups as f32).sqrt().ceil() as usize;
+    let slice_len = slices * NODE_SIZE;
+    entries.sort_by(|a, b| coord(a, 0).partial_cmp(&coord(b, 0)).unwrap());
+    let mut ranges = Vec::with_capacity(groups);
+    for start in (0..entries.len()).step_by(slice_len) {
+        let end = (start + slice_len).min(entries.len());
+        entries[start..end].sort_by(|a, b| coord(a, 1).partial_cmp(&coord(b, 1)).unwrap());
+        for group in (start..end).step_by(NODE_SIZE) {
+            ranges.push(group..(group + NODE_SIZE).min(end));
+        }
+    }
+    ranges
+}
+
+/// A uniform grid with `dims` cells over a bounding box, storing for each cell the items
+/// overlapping it.
+pub struct Grid {
+    min: Point,
+    cell_size: Point,
+    dims: [usize; 2],
+    cells: Vec<u32>,
+    items: Vec<u32>,
+}
+
+impl Grid {
+    pub fn new(min: Point, max: Point, dims: [usize; 2], boxes: &[(Point, Point)]) -> Grid {
+        let dims = [dims[0].max(1), dims[1].max(1)];
+        let mut grid = Grid {
+            min,
+            cell_size: [(max[0] - min[0]) / dims[0] as f32, (max[1] - min[1]) / dims[1] as f32],
+            dims,
+            cells: vec![0; dims[0] * dims[1] + 1],
+            items: Vec::new(),
+        };
+        for b in boxes {
+            let (from, to) = grid.cell_range(b.0, b.1);
+            for y in from[1]..=to[1] {
+                for x in from[0]..=to[0] {
+                    grid.cells[y * dims[0] + x + 1] += 1;
+                }
+            }
+        }
+        for i in 1..grid.cells.len() {
+            grid.cells[i] += grid.cells[i - 1];
+        }
+        let mut fill = grid.cells.clone();
+        grid.items = vec![0; grid.cells[dims[0] * dims[1]] as usize];
+        for (i, b) in boxes.iter().enumerate() {
+            let (from, to) = grid.cell_range(b.0, b.1);
+            for y in from[1]..=to[1] {
+                for x in from[0]..=to[0] {
+                    grid.items[fill[y * dims[0] + x] as usize] = i as u32;
+                    fill[y * dims[0] + x] += 1;
+                }
+            }
+        }
+        grid
+    }
+
+    fn cell(&self, pos: Point, d: usize) -> usize {
+        if self.cell_size[d] > 0.0 {
+            (((pos[d] - self.min[d]) / self.cell_size[d]).max(0.0) as usize).min(self.dims[d] - 1)
+        } else {
+            0
+        }
+    }
+
+    fn cell_range(&self, min: Point, max: Point) -> ([usize; 2], [usize; 2]) {
+        ([self.cell(min, 0), self.cell(min, 1)], [self.cell(max, 0), self.cell(max, 1)])
+    }
+
+    /// Returns the items in the cell containing `pos`.
+    pub fn get(&self, pos: Point) -> &[u32] {
+        let i = self.cell(pos, 1) * self.dims[0] + self.cell(pos, 0);
+        &self.items[self.cells[i] as usize..self.cells[i + 1] as usize]
+    }
+}
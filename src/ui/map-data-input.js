@@ -11,6 +11,16 @@ import DeleteIcon from '../icons/delete.svg';
 
 const data_location = './static/data/';
 
+const PROJECTIONS = [
+    ['WebMercator', 'Web Mercator'],
+    ['Equirectangular', 'Equirectangular'],
+    ['EqualEarth', 'Equal Earth'],
+    ['Robinson', 'Robinson'],
+    ['AlbersEqualArea', 'Albers equal-area'],
+    ['LambertConformalConic', 'Lambert conformal conic'],
+    ['Orthographic', 'Orthographic'],
+];
+
 class MapDataInput extends LitElement {
 
     static get properties() {
@@ -117,17 +127,17 @@ class MapDataInput extends LitElement {
                 display: block;
                 margin: 4px;
             }
-            .title-input-wrap, .defcolor-input-wrap, .a3d-input-wrap, .data-input-wrap, .location-cell {
+            .title-input-wrap, .defcolor-input-wrap, .a3d-input-wrap, .proj-input-wrap, .data-input-wrap, .location-cell {
                 display: flex;
                 align-items: center;
             }
-            .title-input-wrap, .defcolor-input-wrap, .a3d-input-wrap {
+            .title-input-wrap, .defcolor-input-wrap, .a3d-input-wrap, .proj-input-wrap {
                 margin: 0.25rem;
             }
             .title-input-wrap {
                 margin-top: 0.5rem;
             }
-            .title-input-wrap span.title, .defcolor-input-wrap span.defcolor, .a3d-input-wrap span.a3d {
+            .title-input-wrap span.title, .defcolor-input-wrap span.defcolor, .a3d-input-wrap span.a3d, .proj-input-wrap span.proj {
                 margin: 4px;
             }
             input.text-field, input.text-field {
@@ -148,7 +158,7 @@ class MapDataInput extends LitElement {
                 justify-content: space-between;
                 max-width: 20rem;
             }
-            .a3d-input-wrap span.a3d {
+            .a3d-input-wrap span.a3d, .proj-input-wrap span.proj {
                 white-space: nowrap;
             }
             .a3d-input-wrap .a3d-checkbox {
@@ -314,6 +324,15 @@ class MapDataInput extends LitElement {
         });
     }
 
+    updateProjection(kind) {
+        this.data.projection = typeof this.data.projection === 'object'
+            ? { ...this.data.projection, kind }
+            : kind;
+        this.dispatchOnChange({
+            ...this.data,
+        });
+    }
+
     updateAllow3d(allow_3d) {
         this.data.allow_3d = allow_3d;
         this.dispatchOnChange({
@@ -346,6 +365,14 @@ class MapDataInput extends LitElement {
                                     ?checked="${this.data.allow_3d}"
                                     @change="${e => this.updateAllow3d(e.target.checked)}"
                                 /></span>
+                                <span class="proj-input-wrap"><span class="proj">Projection:</span><select
+                                    @change="${e => this.updateProjection(e.target.value)}"
+                                >${PROJECTIONS.map(([value, name]) => html`
+                                    <option
+                                        value="${value}"
+                                        ?selected="${(this.data.projection?.kind ?? this.data.projection ?? 'WebMercator') === value}"
+                                    >${name}</option>
+                                `)}</select></span>
                             </span>
                         </div>
                         <table>